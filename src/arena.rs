@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ArenaBounds>();
+    }
+}
+
+/// The playable area, nothing is allowed outside of it and the camera never shows past it.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ArenaBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Default for ArenaBounds {
+    fn default() -> Self {
+        Self {
+            min: Vec2::splat(-1500.0),
+            max: Vec2::splat(1500.0),
+        }
+    }
+}
+
+impl ArenaBounds {
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, pos: Vec2) -> bool {
+        pos.cmpge(self.min).all() && pos.cmple(self.max).all()
+    }
+
+    /// Moves `pos` so that a box of half size `margin` around it stays inside the bounds. On
+    /// axes where the box doesn't fit it's centered instead.
+    pub fn clamp(&self, pos: Vec2, margin: Vec2) -> Vec2 {
+        let min = self.min + margin;
+        let max = self.max - margin;
+        let center = (self.min + self.max) / 2.0;
+        Vec2::select(min.cmple(max), pos.max(min).min(max), center)
+    }
+}
@@ -0,0 +1,93 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::player::Player;
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ListenerPos>()
+            .add_startup_system(load_sound_effects)
+            .add_system_to_stage(CoreStage::PreUpdate, update_listener_pos);
+    }
+}
+
+/// How far the playback speed (and with it the pitch) of a sound may deviate from normal.
+const PITCH_VARIATION: f32 = 0.08;
+/// Sounds closer than this to the listener play at full volume.
+const FULL_VOLUME_DISTANCE: f32 = 400.0;
+/// Sounds further than this from the listener are inaudible.
+const SILENT_DISTANCE: f32 = 1600.0;
+
+/// Where sounds are heard from, the player, halfway between the players when there are two, or
+/// the camera if there is no player.
+#[derive(Resource, Default)]
+pub struct ListenerPos(pub Vec2);
+
+/// Volume multiplier for a sound at `sound_pos` heard from `listener_pos`, falling off linearly
+/// between [`FULL_VOLUME_DISTANCE`] and [`SILENT_DISTANCE`].
+pub fn attenuation(sound_pos: Vec2, listener_pos: Vec2) -> f32 {
+    let distance = sound_pos.distance(listener_pos);
+    let t = (distance - FULL_VOLUME_DISTANCE) / (SILENT_DISTANCE - FULL_VOLUME_DISTANCE);
+    (1.0 - t).clamp(0.0, 1.0)
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SoundEffects {
+    pub gunshot: Handle<AudioSource>,
+    pub impact: Handle<AudioSource>,
+    pub footstep: Handle<AudioSource>,
+    pub enemy_death: Handle<AudioSource>,
+}
+
+impl SoundEffects {
+    /// Plays `sound` with a slightly randomized pitch so repeated sounds don't feel robotic.
+    pub fn play(&self, audio: &Audio, sound: &Handle<AudioSource>, volume: f32) {
+        let speed = 1.0 + rand::thread_rng().gen_range(-PITCH_VARIATION..PITCH_VARIATION);
+        audio.play_with_settings(
+            sound.clone(),
+            PlaybackSettings::ONCE.with_volume(volume).with_speed(speed),
+        );
+    }
+
+    /// Plays `sound` as if it came from `pos`, quieter the further away it is from the listener.
+    pub fn play_at(
+        &self,
+        audio: &Audio,
+        sound: &Handle<AudioSource>,
+        volume: f32,
+        pos: Vec2,
+        listener: &ListenerPos,
+    ) {
+        let volume = volume * attenuation(pos, listener.0);
+        if volume > 0.0 {
+            self.play(audio, sound, volume);
+        }
+    }
+}
+
+fn update_listener_pos(
+    mut listener: ResMut<ListenerPos>,
+    player: Query<&GlobalTransform, With<Player>>,
+    camera: Query<&GlobalTransform, With<Camera>>,
+) {
+    let count = player.iter().count();
+    if count > 0 {
+        // Halfway between the players when there are two of them
+        let sum: Vec2 = player.iter().map(|tf| tf.translation().xy()).sum();
+        listener.0 = sum / count as f32;
+    } else if let Some(tf) = camera.iter().next() {
+        listener.0 = tf.translation().xy();
+    }
+}
+
+fn load_sound_effects(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SoundEffects {
+        gunshot: asset_server.load("sounds/gunshot.ogg"),
+        impact: asset_server.load("sounds/impact.ogg"),
+        footstep: asset_server.load("sounds/footstep.ogg"),
+        enemy_death: asset_server.load("sounds/enemy_death.ogg"),
+    });
+}
@@ -0,0 +1,188 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use bevy::render::render_resource::{
+    AddressMode, Extent3d, SamplerDescriptor, TextureDimension, TextureFormat,
+};
+use bevy::render::texture::ImageSampler;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::transform::TransformSystem;
+use rand::Rng;
+
+use crate::arena::ArenaBounds;
+use crate::camera::shake_camera;
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_dust)
+            .add_startup_system(spawn_grid)
+            .add_system(resize_grid)
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::new()
+                    .after(shake_camera)
+                    .before(TransformSystem::TransformPropagate)
+                    .with_system(scroll_parallax),
+            );
+    }
+}
+
+/// Number of specks of dust in the distant background layer.
+const DUST_COUNT: usize = 300;
+const DUST_PARALLAX: f32 = 0.4;
+const DUST_Z: f32 = -10.0;
+const GRID_Z: f32 = -50.0;
+/// Side length of a grid cell, in pixels both in the world and in the grid texture.
+const GRID_CELL: u32 = 64;
+const GRID_LINE_COLOR: [u8; 4] = [255, 255, 255, 20];
+
+/// Moves an entity by `factor` of how the world moves on screen as the camera moves, making it
+/// look further away the closer `factor` is to 0. At 0 it stays in the same place on screen, at 1
+/// it moves with the world like everything else.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Parallax {
+    pub factor: f32,
+    /// Where the entity is when the camera is at the origin.
+    pub origin: Vec2,
+}
+
+/// A single quad covering the arena with a repeating grid texture on it. The cameras never show
+/// anything outside the arena, so this covers every view however the window is split.
+#[derive(Component)]
+struct Grid;
+
+/// A single grid cell with a line along its top and left edge, tiled across the grid.
+fn grid_cell_image() -> Image {
+    let size = GRID_CELL as usize;
+    let mut data = vec![0; size * size * 4];
+    for y in 0..size {
+        for x in 0..size {
+            if x == 0 || y == 0 {
+                let i = 4 * (y * size + x);
+                data[i..i + 4].copy_from_slice(&GRID_LINE_COLOR);
+            }
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width: GRID_CELL,
+            height: GRID_CELL,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor = ImageSampler::Descriptor(SamplerDescriptor {
+        address_mode_u: AddressMode::Repeat,
+        address_mode_v: AddressMode::Repeat,
+        ..default()
+    });
+    image
+}
+
+/// A quad large enough to cover an area of the given size.
+fn grid_mesh(area: Vec2) -> Mesh {
+    // A cell of margin on every side so the edges never show when lined up with the cells
+    let cell = GRID_CELL as f32;
+    let cells = (area / cell).ceil() + 2.0;
+
+    let mut mesh = Mesh::from(shape::Quad::new(cells * cell));
+    // Repeat the texture once per cell
+    if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        for uv in uvs {
+            uv[0] *= cells.x;
+            uv[1] *= cells.y;
+        }
+    }
+    mesh
+}
+
+/// The center of the arena snapped to a whole cell, so the lines are at multiples of the cell
+/// size in the world.
+fn grid_center(bounds: &ArenaBounds) -> Vec2 {
+    let cell = GRID_CELL as f32;
+    ((bounds.min + bounds.max) / 2.0 / cell).round() * cell
+}
+
+fn spawn_grid(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    bounds: Res<ArenaBounds>,
+) {
+    let mesh = grid_mesh(bounds.size());
+    commands.spawn((
+        Name::new("Grid"),
+        Grid,
+        MaterialMesh2dBundle {
+            mesh: meshes.add(mesh).into(),
+            material: materials.add(ColorMaterial::from(images.add(grid_cell_image()))),
+            transform: Transform::from_translation(grid_center(&bounds).extend(GRID_Z)),
+            ..default()
+        },
+    ));
+}
+
+/// Keeps the grid covering the whole arena when its bounds change.
+fn resize_grid(
+    bounds: Res<ArenaBounds>,
+    mut grid: Query<(&mut Mesh2dHandle, &mut Transform), With<Grid>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !bounds.is_changed() || bounds.is_added() {
+        return;
+    }
+    for (mut mesh, mut tf) in &mut grid {
+        *mesh = meshes.add(grid_mesh(bounds.size())).into();
+        tf.translation = grid_center(&bounds).extend(GRID_Z);
+    }
+}
+
+fn spawn_dust(mut commands: Commands, bounds: Res<ArenaBounds>) {
+    let mut rng = rand::thread_rng();
+    // The layer moves by less than the world, so it only needs to cover part of the arena
+    let half_extent = DUST_PARALLAX * bounds.size() / 2.0 + Vec2::new(600.0, 400.0);
+    for _ in 0..DUST_COUNT {
+        let origin = Vec2::new(
+            rng.gen_range(-half_extent.x..half_extent.x),
+            rng.gen_range(-half_extent.y..half_extent.y),
+        );
+        commands.spawn((
+            Name::new("Dust"),
+            Parallax {
+                factor: DUST_PARALLAX,
+                origin,
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(1.0, 1.0, 1.0, rng.gen_range(0.05..0.2)),
+                    custom_size: Some(Vec2::splat(rng.gen_range(1.0..3.0))),
+                    ..default()
+                },
+                transform: Transform::from_translation(origin.extend(DUST_Z)),
+                ..default()
+            },
+        ));
+    }
+}
+
+/// Scrolls the layers along with the first camera. With split screen the other views show them
+/// wherever that camera puts them.
+fn scroll_parallax(
+    cameras: Query<(&Camera, &Transform)>,
+    mut layers: Query<(&mut Transform, &Parallax), Without<Camera>>,
+) {
+    let first = cameras.iter().min_by_key(|(camera, _)| camera.priority);
+    let Some((_, camera_tf)) = first else { return };
+    let camera_pos = camera_tf.translation.xy();
+    for (mut tf, parallax) in &mut layers {
+        // The camera drags the layer along by however much it doesn't move with the world
+        let pos = parallax.origin + (1.0 - parallax.factor) * camera_pos;
+        tf.translation = pos.extend(tf.translation.z);
+    }
+}
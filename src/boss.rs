@@ -0,0 +1,363 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+
+use crate::arena::ArenaBounds;
+use crate::audio::{ListenerPos, SoundEffects};
+use crate::bullet::{
+    fire_bullets, BulletEffects, BulletRange, BulletStats, FireBullet, Lifetime, Shooter,
+};
+use crate::camera::ScreenShake;
+use crate::collision::enemy_groups;
+use crate::difficulty::DifficultyScaling;
+use crate::enemy::{nearest, wall_filter, AiState, Enemy, PointValue, Stunned, WaveSpawner};
+use crate::fixed::Interpolated;
+use crate::game_time::GameTime;
+use crate::health::{Faction, Health};
+use crate::pickup::{DropTable, EnemyDrops};
+use crate::player::Player;
+use crate::rng::GameRng;
+use crate::state::{GameState, GameplayEntity};
+
+/// Sends a boss after the player every few waves, with attacks that get more dangerous as it
+/// loses health.
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BossDefeated>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(spawn_bosses)
+                .with_system(update_boss_phase)
+                .with_system(boss_attack.after(update_boss_phase).before(fire_bullets))
+                .with_system(boss_death)
+                .with_system(start_death_explosions.after(boss_death))
+                .with_system(play_death_explosions.after(start_death_explosions)),
+        );
+    }
+}
+
+/// A boss comes along with every wave that's a multiple of this.
+const BOSS_WAVE_INTERVAL: usize = 5;
+const BOSS_RADIUS: f32 = 60.0;
+const BOSS_HEALTH: f32 = 1500.0;
+const BOSS_POINTS: u32 = 250;
+/// Distance from the player bosses appear at.
+const BOSS_SPAWN_DISTANCE: f32 = 600.0;
+/// Seconds a boss holds its fire after entering a new phase.
+const PHASE_TRANSITION_PAUSE: f32 = 1.5;
+/// Angle covered by the fan of bullets fired in the spread phase.
+const SPREAD_ANGLE: f32 = 0.8;
+const SPREAD_BULLETS: usize = 5;
+/// Radians the spiral turns between two shots.
+const SPIRAL_STEP: f32 = 0.3;
+const RING_BULLETS: usize = 16;
+/// Number of explosions going off after a boss dies.
+const DEATH_EXPLOSIONS: u32 = 8;
+/// Seconds between two explosions after a boss dies.
+const DEATH_EXPLOSION_INTERVAL: f32 = 0.15;
+
+/// Sent when a boss is killed.
+pub struct BossDefeated {
+    pub pos: Vec2,
+}
+
+/// How a boss attacks, which depends on how much health it has left.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BossPhase {
+    /// Fans of bullets aimed at the player.
+    #[default]
+    Spread,
+    /// Two arms of bullets spiralling out of the boss.
+    Spiral,
+    /// Rings of bullets in every direction.
+    Frenzy,
+}
+
+impl BossPhase {
+    /// The phase a boss with `health` fights in, a new one for every third of its health lost.
+    fn for_health(health: &Health) -> Self {
+        let fraction = health.current / health.max;
+        if fraction > 2.0 / 3.0 {
+            BossPhase::Spread
+        } else if fraction > 1.0 / 3.0 {
+            BossPhase::Spiral
+        } else {
+            BossPhase::Frenzy
+        }
+    }
+
+    /// Seconds between two attacks in this phase.
+    fn interval(self) -> f32 {
+        match self {
+            BossPhase::Spread => 1.2,
+            BossPhase::Spiral => 0.08,
+            BossPhase::Frenzy => 0.7,
+        }
+    }
+}
+
+#[derive(Component, Debug, Default)]
+pub struct Boss {
+    pub phase: BossPhase,
+    /// Time until the next attack.
+    pub cooldown: f32,
+    /// Where the spiral or ring of bullets is currently turned to, in radians.
+    angle: f32,
+}
+
+/// Plays a string of explosions around where a boss died.
+#[derive(Component)]
+struct DeathExplosions {
+    left: u32,
+    timer: Timer,
+}
+
+fn boss_bullet() -> BulletStats {
+    BulletStats {
+        speed: 400.0,
+        damage: 12.0,
+        size: 10.0,
+        crit_chance: 0.0,
+        range: BulletRange::Time(3.0),
+        color: Color::VIOLET,
+        ..default()
+    }
+}
+
+pub fn spawn_boss(
+    commands: &mut Commands,
+    pos: Vec2,
+    drops: DropTable,
+    difficulty: &DifficultyScaling,
+) -> Entity {
+    commands
+        .spawn((
+            Name::new("Boss"),
+            Enemy,
+            Boss::default(),
+            GameplayEntity,
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::INDIGO,
+                    custom_size: Some(Vec2::splat(2.0 * BOSS_RADIUS)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(0.0)),
+                ..default()
+            },
+            Interpolated::new(pos.extend(0.0)),
+            (
+                RigidBody::KinematicPositionBased,
+                Collider::ball(BOSS_RADIUS),
+                enemy_groups(),
+                ActiveEvents::COLLISION_EVENTS,
+                ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_KINEMATIC,
+            ),
+            Health::new(BOSS_HEALTH * difficulty.enemy_health),
+            Faction::Enemy,
+            PointValue(BOSS_POINTS),
+            // Bosses always drop something
+            DropTable {
+                chance: 1.0,
+                ..drops
+            },
+            AiState::Chasing,
+        ))
+        .id()
+}
+
+/// Spawns a boss near the player once every few waves, unless one is still alive.
+fn spawn_bosses(
+    mut commands: Commands,
+    spawner: Res<WaveSpawner>,
+    bosses: Query<(), With<Boss>>,
+    player: Query<&Transform, With<Player>>,
+    rapier: Res<RapierContext>,
+    bounds: Res<ArenaBounds>,
+    drops: Res<EnemyDrops>,
+    difficulty: Res<DifficultyScaling>,
+    mut rng: ResMut<GameRng>,
+    mut handled_wave: Local<usize>,
+) {
+    if spawner.wave == *handled_wave {
+        return;
+    }
+    if spawner.wave == 0 || spawner.wave % BOSS_WAVE_INTERVAL != 0 || !bosses.is_empty() {
+        *handled_wave = spawner.wave;
+        return;
+    }
+    // Near one of the players, it goes after whichever is nearest once it's in the arena
+    let Some(player_tf) = player.iter().next() else { return };
+
+    let angle = rng.gen_range(0.0..TAU);
+    let pos = player_tf.translation.xy() + BOSS_SPAWN_DISTANCE * Vec2::from_angle(angle);
+    let pos = bounds.clamp(pos, Vec2::splat(BOSS_RADIUS));
+    let shape = Collider::ball(BOSS_RADIUS);
+    // Inside a wall, try somewhere else next frame
+    if rapier
+        .intersection_with_shape(pos, 0.0, &shape, wall_filter())
+        .is_some()
+    {
+        return;
+    }
+    spawn_boss(&mut commands, pos, drops.0.clone(), &difficulty);
+    *handled_wave = spawner.wave;
+}
+
+fn update_boss_phase(
+    mut bosses: Query<(&mut Boss, &Health), Changed<Health>>,
+    mut shake: ResMut<ScreenShake>,
+) {
+    for (mut boss, health) in &mut bosses {
+        let phase = BossPhase::for_health(health);
+        if boss.phase != phase {
+            boss.phase = phase;
+            boss.cooldown = PHASE_TRANSITION_PAUSE;
+            shake.add_trauma(0.5);
+        }
+    }
+}
+
+/// Fires the attack pattern of each boss's current phase.
+fn boss_attack(
+    mut bosses: Query<(Entity, &Transform, &mut Boss), Without<Stunned>>,
+    player: Query<&Transform, With<Player>>,
+    mut fire: EventWriter<FireBullet>,
+    audio: Res<Audio>,
+    sounds: Res<SoundEffects>,
+    listener: Res<ListenerPos>,
+    time: Res<GameTime>,
+) {
+    let players: Vec<Vec2> = player.iter().map(|tf| tf.translation.xy()).collect();
+
+    for (entity, tf, mut boss) in &mut bosses {
+        boss.cooldown = (boss.cooldown - time.delta_seconds()).max(0.0);
+        if boss.cooldown > 0.0 {
+            continue;
+        }
+        let pos = tf.translation.xy();
+        let Some(target) = nearest(players.iter().copied(), pos) else { continue };
+        boss.cooldown = boss.phase.interval();
+
+        let angles: Vec<f32> = match boss.phase {
+            BossPhase::Spread => {
+                let to_target = target - pos;
+                let aim = to_target.y.atan2(to_target.x);
+                let step = SPREAD_ANGLE / (SPREAD_BULLETS - 1) as f32;
+                (0..SPREAD_BULLETS)
+                    .map(|i| aim - SPREAD_ANGLE / 2.0 + i as f32 * step)
+                    .collect()
+            }
+            BossPhase::Spiral => {
+                boss.angle += SPIRAL_STEP;
+                vec![boss.angle, boss.angle + PI]
+            }
+            BossPhase::Frenzy => {
+                // Every other ring is turned by half a gap, so standing in a gap isn't safe
+                let step = TAU / RING_BULLETS as f32;
+                boss.angle += step / 2.0;
+                (0..RING_BULLETS)
+                    .map(|i| boss.angle + i as f32 * step)
+                    .collect()
+            }
+        };
+
+        let shooter = Shooter {
+            entity,
+            faction: Faction::Enemy,
+            velocity: Vec2::ZERO,
+            pos,
+            radius: BOSS_RADIUS,
+        };
+        for angle in angles {
+            fire.send(FireBullet {
+                origin: tf.translation,
+                dir: Vec2::from_angle(angle),
+                stats: boss_bullet(),
+                shooter,
+            });
+        }
+        sounds.play_at(&audio, &sounds.gunshot, 0.5, pos, &listener);
+    }
+}
+
+/// Announces dead bosses, `enemy_death` takes care of despawning them like any other enemy.
+fn boss_death(
+    bosses: Query<(&Transform, &Health), With<Boss>>,
+    mut defeated: EventWriter<BossDefeated>,
+) {
+    for (tf, health) in &bosses {
+        if health.is_dead() {
+            defeated.send(BossDefeated {
+                pos: tf.translation.xy(),
+            });
+        }
+    }
+}
+
+fn start_death_explosions(mut commands: Commands, mut defeated: EventReader<BossDefeated>) {
+    for boss in defeated.iter() {
+        commands.spawn((
+            Name::new("Boss death explosions"),
+            GameplayEntity,
+            SpatialBundle::from_transform(Transform::from_translation(boss.pos.extend(0.0))),
+            DeathExplosions {
+                left: DEATH_EXPLOSIONS,
+                timer: Timer::from_seconds(DEATH_EXPLOSION_INTERVAL, TimerMode::Repeating),
+            },
+        ));
+    }
+}
+
+/// Sets off explosions at random points around where the boss died, building up to a last big
+/// one in the middle.
+fn play_death_explosions(
+    mut commands: Commands,
+    mut explosions: Query<(Entity, &Transform, &mut DeathExplosions)>,
+    effects: Option<Res<BulletEffects>>,
+    audio: Res<Audio>,
+    sounds: Res<SoundEffects>,
+    listener: Res<ListenerPos>,
+    mut shake: ResMut<ScreenShake>,
+    mut rng: ResMut<GameRng>,
+    time: Res<GameTime>,
+) {
+    for (entity, tf, mut explosions) in &mut explosions {
+        if !explosions.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        explosions.left -= 1;
+        let last = explosions.left == 0;
+
+        let center = tf.translation.xy();
+        let (pos, scale) = if last {
+            (center, 3.0)
+        } else {
+            let offset = rng.gen_range(0.0..BOSS_RADIUS * 1.5);
+            let pos = center + offset * Vec2::from_angle(rng.gen_range(0.0..TAU));
+            (pos, rng.gen_range(0.8..1.5))
+        };
+        if let Some(effects) = &effects {
+            commands.spawn((
+                Name::new("Explosion particles"),
+                GameplayEntity,
+                SpatialBundle::from_transform(
+                    Transform::from_translation(pos.extend(0.0)).with_scale(Vec3::splat(scale)),
+                ),
+                ParticleEffect::new(effects.explosion.clone()).with_z_layer_2d(Some(0.3)),
+                Lifetime(5.0),
+            ));
+        }
+        sounds.play_at(&audio, &sounds.enemy_death, 0.8, pos, &listener);
+        shake.add_trauma(if last { 1.0 } else { 0.3 });
+
+        if last {
+            commands.entity(entity).despawn();
+        }
+    }
+}
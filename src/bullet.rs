@@ -5,106 +5,897 @@ use bevy::prelude::*;
 use bevy_hanabi::prelude::*;
 use bevy_hanabi::EffectAsset;
 use bevy_rapier2d::prelude::*;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-pub struct BulletPlugin;
+use crate::arena::ArenaBounds;
+use crate::audio::{ListenerPos, SoundEffects};
+use crate::camera::ScreenShake;
+use crate::collision::bullet_groups;
+use crate::enemy::Stunned;
+use crate::fixed::{FixedStep, FixedUpdateStage, Interpolated, Simulation};
+use crate::game_time::GameTime;
+use crate::health::{DamageDealt, Faction, Health, Invulnerable};
+use crate::hot_reload::{load_ron_or, read_ron, FileWatcher};
+use crate::player::Dash;
+use crate::rng::GameRng;
+use crate::settings::GraphicsQuality;
+use crate::spatial::EnemyGrid;
+use crate::state::{GameState, GameplayEntity};
+use crate::tuning::{override_tuning, Tuning};
+use crate::wall::{PassableFrom, SurfaceMaterial, Wall};
+
+/// Bullets and their effects. How fast and for how long ordinary bullets fly comes from the
+/// [`Tuning`] unless set here, e.g. `BulletPlugin::default().with_speed(2000.0)`.
+#[derive(Default)]
+pub struct BulletPlugin {
+    speed: Option<f32>,
+    lifetime: Option<f32>,
+}
+
+impl BulletPlugin {
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    /// Seconds bullets fly for unless their weapon gives them a different range.
+    pub fn with_lifetime(mut self, lifetime: f32) -> Self {
+        self.lifetime = Some(lifetime);
+        self
+    }
+}
 
 impl Plugin for BulletPlugin {
     fn build(&self, app: &mut App) {
+        if let Some(speed) = self.speed {
+            override_tuning(app, move |tuning| tuning.bullet_speed = speed);
+        }
+        if let Some(lifetime) = self.lifetime {
+            override_tuning(app, move |tuning| tuning.bullet_lifetime = lifetime);
+        }
         app.register_type::<Lifetime>()
             .register_type::<Bullet>()
-            .add_system(Bullet::move_bullet)
+            .init_resource::<FriendlyFire>()
+            .init_resource::<SweptCollision>()
+            .add_event::<FireBullet>()
             .add_startup_system(setup_bullet_trail)
-            .add_system(Bullet::cleanup)
-            .add_system(despawn_after_lifetime);
+            .add_system(reload_bullet_effects)
+            .add_system(fire_bullets)
+            .add_system_to_stage(FixedUpdateStage, Bullet::move_bullet.label(Simulation))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(despawn_after_lifetime)
+                    .with_system(fade_out.before(despawn_after_lifetime))
+                    .with_system(limit_decals),
+            )
+            .add_system(Bullet::cleanup);
+    }
+}
+
+const DAMAGE: f32 = 25.0;
+const SIZE: f32 = 3.0;
+const CRIT_CHANCE: f32 = 0.05;
+const CRIT_MULT: f32 = 2.0;
+/// Gap left between a bullet and its shooter's collider when it's fired.
+const SPAWN_CLEARANCE: f32 = 2.0;
+const DECAL_LIFETIME: f32 = 10.0;
+const DECAL_ALPHA: f32 = 0.6;
+/// Most bullet holes there can be at once, the oldest ones are removed first.
+const MAX_DECALS: usize = 128;
+/// Files the effects can be tuned in, as a RON `EffectAsset`. The built-in effects are used for
+/// missing files.
+const TRAIL_EFFECT_PATH: &str = "assets/effects/bullet_trail.ron";
+const DEBRIS_EFFECT_PATH: &str = "assets/effects/debris.ron";
+const METAL_DEBRIS_EFFECT_PATH: &str = "assets/effects/debris_metal.ron";
+const WOOD_DEBRIS_EFFECT_PATH: &str = "assets/effects/debris_wood.ron";
+const STONE_DEBRIS_EFFECT_PATH: &str = "assets/effects/debris_stone.ron";
+const EXIT_DEBRIS_EFFECT_PATH: &str = "assets/effects/debris_exit.ron";
+const EXPLOSION_EFFECT_PATH: &str = "assets/effects/explosion.ron";
+/// Fraction of its speed a bullet keeps when bouncing off a wall.
+const BOUNCE_DAMPING: f32 = 0.6;
+/// How far off a wall a bouncing bullet is put, so it doesn't hit the same wall again.
+const BOUNCE_OFFSET: f32 = 0.5;
+/// Bullets hitting a wall at a shallower angle than this, as its sine, stop instead of bouncing
+/// along it.
+const MIN_BOUNCE_SINE: f32 = 0.05;
+/// Seconds between checks whether the effect files have changed.
+const EFFECT_RELOAD_INTERVAL: f32 = 0.5;
+
+/// How far a bullet flies before it disappears, if it doesn't hit anything first.
+#[derive(Reflect, FromReflect, Debug, Clone, Copy, PartialEq)]
+pub enum BulletRange {
+    /// Flies for this many seconds.
+    Time(f32),
+    /// Flies this far, however fast it is.
+    Distance(f32),
+}
+
+/// How a bullet's damage drops off with the distance it has flown.
+#[derive(Reflect, FromReflect, Debug, Clone, Copy, PartialEq)]
+pub struct DamageFalloff {
+    /// Distance up to which the bullet deals its full damage.
+    pub start: f32,
+    /// Distance from which on the bullet only deals `min_damage`.
+    pub end: f32,
+    pub min_damage: f32,
+}
+
+impl DamageFalloff {
+    /// The damage a bullet dealing `damage` up close deals after flying `traveled`, dropping
+    /// linearly between `start` and `end`. Never more than `damage`, even if that's below
+    /// `min_damage`.
+    pub fn damage_at(&self, damage: f32, traveled: f32) -> f32 {
+        let t =
+            ((traveled - self.start) / (self.end - self.start).max(f32::EPSILON)).clamp(0.0, 1.0);
+        let min_damage = self.min_damage.min(damage);
+        damage + t * (min_damage - damage)
+    }
+}
+
+/// What a bullet does when fired, everything but where and by whom.
+#[derive(Debug, Clone, Copy)]
+pub struct BulletStats {
+    pub speed: f32,
+    pub damage: f32,
+    /// Diameter the bullet is drawn at, also used for swept collision. How far it checks for
+    /// hits each step only depends on its speed.
+    pub size: f32,
+    /// Downwards acceleration making the bullet fly in an arc, zero for straight shots.
+    pub gravity: f32,
+    /// Radius of the explosion on impact, zero for bullets that only hit what they touch.
+    pub explosive: f32,
+    /// Whether the bullet explodes once its range runs out instead of just disappearing, like a
+    /// grenade with a fuse.
+    pub fused: bool,
+    /// How many times the bullet bounces off walls before hitting one stops it.
+    pub bounces: u32,
+    /// How many targets the bullet passes through, damaging each, before hitting one stops it.
+    pub pierce: u32,
+    /// Seconds its explosion stuns enemies for, instead of damaging them. Zero for explosions
+    /// that deal damage.
+    pub stun: f32,
+    /// How fast, in radians per second, the bullet turns towards the nearest enemy.
+    pub homing: f32,
+    /// Probability of a hit being critical.
+    pub crit_chance: f32,
+    /// Damage multiplier of critical hits.
+    pub crit_mult: f32,
+    pub range: BulletRange,
+    /// How damage drops off with distance, `None` to deal the same damage at any distance.
+    pub falloff: Option<DamageFalloff>,
+    /// Color the bullet is drawn in, so shots from different weapons can be told apart.
+    pub color: Color,
+}
+
+impl Default for BulletStats {
+    /// The stats of an ordinary bullet with the built-in tuning, ignoring the tuning file and
+    /// any [`TuningOverrides`](crate::tuning::TuningOverrides). Fine for bullets that set their
+    /// own speed and range, anything else should start from [`BulletStats::tuned`].
+    fn default() -> Self {
+        Self::tuned(&Tuning::default())
+    }
+}
+
+impl BulletStats {
+    /// The stats of an ordinary bullet with the given tuning.
+    pub fn tuned(tuning: &Tuning) -> Self {
+        Self {
+            speed: tuning.bullet_speed,
+            damage: DAMAGE,
+            size: SIZE,
+            gravity: 0.0,
+            explosive: 0.0,
+            fused: false,
+            bounces: 0,
+            pierce: 0,
+            stun: 0.0,
+            homing: 0.0,
+            crit_chance: CRIT_CHANCE,
+            crit_mult: CRIT_MULT,
+            range: BulletRange::Time(tuning.bullet_lifetime),
+            falloff: None,
+            color: Color::YELLOW,
+        }
     }
 }
 
-const SPEED: f32 = 1500.0;
+/// Whether bullets can hit entities of the same faction as their shooter.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct FriendlyFire(pub bool);
+
+/// When enabled, bullets sweep a ball of their actual size instead of casting an infinitely thin
+/// ray, so grazing hits on small colliders aren't missed.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct SweptCollision(pub bool);
+
+/// Where a bullet hit something this frame.
+struct Impact {
+    entity: Entity,
+    point: Vec2,
+    normal: Vec2,
+}
+
+/// Reflects `dir` off a surface with the given `normal`.
+fn reflect(dir: Vec2, normal: Vec2) -> Vec2 {
+    dir - 2.0 * dir.dot(normal) * normal
+}
+
+/// Bounces a bullet flying along `dir` off a wall it hit with the given `normal`, returning its
+/// new direction and the way to move it off the wall. At corners the normal can be degenerate,
+/// or face away from the bullet so that reflecting would send it into the wall, then it flies
+/// back the way it came instead. Grazing hits don't bounce, as the bullet would keep skimming
+/// along the wall and hitting it again.
+pub fn ricochet(dir: Vec2, normal: Vec2) -> Option<(Vec2, Vec2)> {
+    let back = (-dir, -dir.normalize_or_zero());
+    let Some(normal) = normal.try_normalize() else { return Some(back) };
+    if dir.dot(normal) >= 0.0 {
+        return Some(back);
+    }
+    let reflected = reflect(dir, normal);
+    if reflected.normalize_or_zero().dot(normal) < MIN_BOUNCE_SINE {
+        return None;
+    }
+    Some((reflected, normal))
+}
+
+/// Rotation turning the x axis towards `dir`.
+fn facing(dir: Vec2) -> Quat {
+    Quat::from_rotation_z(dir.y.atan2(dir.x))
+}
 
 #[derive(Reflect, Component)]
 pub struct Bullet {
-    lifetime: f32,
+    range: BulletRange,
+    /// Seconds since the bullet was fired.
+    age: f32,
+    /// Distance flown since the bullet was fired.
+    traveled: f32,
     dir: Vec2,
+    damage: f32,
+    radius: f32,
+    gravity: f32,
+    explosive: f32,
+    fused: bool,
+    bounces: u32,
+    pierce: u32,
+    /// Bounces and pierces the bullet was fired with, which it gets back when it's reset.
+    fired_bounces: u32,
+    fired_pierce: u32,
+    stun: f32,
+    homing: f32,
+    crit_chance: f32,
+    crit_mult: f32,
+    falloff: Option<DamageFalloff>,
+    #[reflect(ignore)]
+    target: Option<Entity>,
+    /// Targets the bullet has passed through, which it can't hit again.
+    #[reflect(ignore)]
+    pierced: Vec<Entity>,
+    #[reflect(ignore)]
+    owner: Entity,
+    #[reflect(ignore)]
+    faction: Faction,
+}
+
+/// Who fired a bullet.
+#[derive(Debug, Clone, Copy)]
+pub struct Shooter {
+    pub entity: Entity,
+    pub faction: Faction,
+    /// Added to the bullet's velocity, so shots fired on the move carry the shooter's momentum.
+    pub velocity: Vec2,
+    /// Center of the shooter's collider.
+    pub pos: Vec2,
+    /// Radius of the shooter's collider, bullets start outside of it so they don't clip through
+    /// the shooter or hit it right away.
+    pub radius: f32,
+}
+
+impl Shooter {
+    /// Moves `pos` along `dir` until a bullet of `radius` there no longer overlaps the shooter.
+    fn clear_of(&self, pos: Vec2, dir: Vec2, radius: f32) -> Vec2 {
+        let reach = self.radius + radius + SPAWN_CLEARANCE;
+        let offset = pos - self.pos;
+        let inside = offset.length_squared() - reach * reach;
+        if inside >= 0.0 {
+            return pos;
+        }
+        // Where a ray from `pos` along `dir` leaves the circle the bullet has to clear
+        let along = offset.dot(dir);
+        pos + (-along + (along * along - inside).sqrt()) * dir
+    }
+}
+
+/// Fires a bullet from `origin` in `dir`, the way every bullet in the game is fired. Senders
+/// should run before [`fire_bullets`] so the bullet flies from the next simulation step on.
+#[derive(Debug, Clone, Copy)]
+pub struct FireBullet {
+    pub origin: Vec3,
+    pub dir: Vec2,
+    pub stats: BulletStats,
+    pub shooter: Shooter,
+}
+
+/// Spawns the bullets of every [`FireBullet`] sent, with a trail once the effects are set up.
+pub fn fire_bullets(
+    mut commands: Commands,
+    mut fired: EventReader<FireBullet>,
+    effects: Option<Res<BulletEffects>>,
+) {
+    for fire in fired.iter() {
+        Bullet::spawn(
+            &mut commands,
+            fire.origin,
+            fire.dir,
+            fire.stats,
+            fire.shooter,
+            effects.as_ref().map(|effects| effects.trail.clone()),
+        );
+    }
 }
 
 impl Bullet {
-    pub fn spawn(commands: &mut Commands, pos: Vec3, dir: Vec2, trail: Handle<EffectAsset>) {
-        commands.spawn((
+    fn spawn(
+        commands: &mut Commands,
+        pos: Vec3,
+        dir: Vec2,
+        stats: BulletStats,
+        shooter: Shooter,
+        trail: Option<Handle<EffectAsset>>,
+    ) {
+        let velocity = dir.normalize() * stats.speed + shooter.velocity;
+        let pos = shooter
+            .clear_of(pos.xy(), dir.normalize(), stats.size / 2.0)
+            .extend(pos.z);
+        let mut state = Bullet {
+            range: stats.range,
+            age: 0.0,
+            traveled: 0.0,
+            dir: Vec2::ZERO,
+            damage: stats.damage,
+            radius: stats.size / 2.0,
+            gravity: stats.gravity,
+            explosive: stats.explosive,
+            fused: stats.fused,
+            bounces: stats.bounces,
+            pierce: stats.pierce,
+            fired_bounces: stats.bounces,
+            fired_pierce: stats.pierce,
+            stun: stats.stun,
+            homing: stats.homing,
+            crit_chance: stats.crit_chance,
+            crit_mult: stats.crit_mult,
+            falloff: stats.falloff,
+            target: None,
+            pierced: Vec::new(),
+            owner: shooter.entity,
+            faction: shooter.faction,
+        };
+        state.reset(velocity, stats.range);
+        let mut bullet = commands.spawn((
             Name::new("Bullet"),
-            Bullet {
-                lifetime: 1.0,
-                dir: dir.normalize() * SPEED,
-            },
+            GameplayEntity,
+            state,
+            bullet_groups(),
             SpriteBundle {
                 sprite: Sprite {
-                    color: Color::YELLOW,
-                    custom_size: Some(Vec2::splat(3.0)),
+                    color: stats.color,
+                    custom_size: Some(Vec2::splat(stats.size)),
                     ..default()
                 },
-                transform: Transform::from_translation(pos),
+                transform: Transform::from_translation(pos).with_rotation(facing(velocity)),
                 ..default()
             },
-            ParticleEffect::new(trail).with_z_layer_2d(Some(0.1)),
+            Interpolated::new(pos),
         ));
+        if let Some(trail) = trail {
+            bullet.insert(ParticleEffect::new(trail).with_z_layer_2d(Some(0.1)));
+        }
+    }
+
+    /// Finds what, if anything, the bullet hits when moving from `pos` during the next `dt`
+    /// seconds.
+    fn cast(
+        &self,
+        rapier: &RapierContext,
+        pos: Vec2,
+        dt: f32,
+        filter: QueryFilter,
+        swept: bool,
+    ) -> Option<Impact> {
+        if swept {
+            let shape = Collider::ball(self.radius);
+            let (entity, toi) = rapier.cast_shape(pos, 0.0, self.dir, &shape, dt, filter)?;
+            // The first shape is the bullet, which isn't rotated, so its local space is aligned
+            // with the world and `normal1` points out of it towards the surface it hit. The
+            // second shape's values are local to the collider that was hit, however it's turned.
+            Some(Impact {
+                entity,
+                point: pos + self.dir * toi.toi + toi.witness1,
+                normal: -toi.normal1,
+            })
+        } else {
+            // `dir` is the velocity, so a time of impact of `dt` is the distance moved this step
+            let (entity, intersection) =
+                rapier.cast_ray_and_get_normal(pos, self.dir, dt, true, filter)?;
+            Some(Impact {
+                entity,
+                point: intersection.point,
+                normal: intersection.normal,
+            })
+        }
+    }
+
+    /// Turns the bullet towards its target, picking the nearest enemy as the target first if it
+    /// doesn't have one yet. Once the target is gone the bullet flies straight.
+    fn steer(&mut self, enemies: &EnemyGrid, pos: Vec2, dt: f32) {
+        if self.homing <= 0.0 {
+            return;
+        }
+
+        if self.target.is_none() {
+            self.target = enemies.nearest(pos).map(|(entity, _)| entity);
+        }
+        let Some(target) = self.target else { return };
+        let Some(target_pos) = enemies.position(target) else {
+            self.homing = 0.0;
+            return;
+        };
+
+        // Right on top of the target, or standing still, there's no way to turn towards it
+        let to_target = target_pos - pos;
+        if to_target.length_squared() < f32::EPSILON || self.dir == Vec2::ZERO {
+            return;
+        }
+        let max_turn = self.homing * dt;
+        let turn = self.dir.angle_between(to_target).clamp(-max_turn, max_turn);
+        self.dir = Vec2::from_angle(turn).rotate(self.dir);
+    }
+
+    pub fn velocity(&self) -> Vec2 {
+        self.dir
+    }
+
+    /// Starts the bullet over as if it was just fired with velocity `dir`, with all of `range`
+    /// ahead of it, all its bounces and pierces left and no homing target yet.
+    pub fn reset(&mut self, dir: Vec2, range: BulletRange) {
+        self.dir = dir;
+        self.range = range;
+        self.age = 0.0;
+        self.traveled = 0.0;
+        self.bounces = self.fired_bounces;
+        self.pierce = self.fired_pierce;
+        self.target = None;
+        self.pierced.clear();
+    }
+
+    /// Seconds until the bullet's range runs out. For bullets with a range in distance, that's
+    /// at the speed it's flying at now.
+    pub fn remaining_lifetime(&self) -> f32 {
+        match self.range {
+            BulletRange::Time(lifetime) => (lifetime - self.age).max(0.0),
+            BulletRange::Distance(distance) => {
+                let speed = self.dir.length();
+                if speed > 0.0 {
+                    (distance - self.traveled).max(0.0) / speed
+                } else {
+                    f32::INFINITY
+                }
+            }
+        }
+    }
+
+    fn out_of_range(&self) -> bool {
+        match self.range {
+            BulletRange::Time(lifetime) => self.age >= lifetime,
+            BulletRange::Distance(distance) => self.traveled >= distance,
+        }
+    }
+
+    /// Rolls whether this hit is critical, and the damage it deals at the distance flown so far.
+    fn roll_damage(&self, rng: &mut impl Rng) -> (f32, bool) {
+        let damage = self.falloff.map_or(self.damage, |falloff| {
+            falloff.damage_at(self.damage, self.traveled)
+        });
+        if rng.gen::<f32>() < self.crit_chance {
+            (damage * self.crit_mult, true)
+        } else {
+            (damage, false)
+        }
     }
 
     fn move_bullet(
         mut commands: Commands,
-        mut bullets: Query<(Entity, &mut Transform, &mut Bullet)>,
+        mut bullets: Query<(Entity, &mut Transform, &mut Bullet, &CollisionGroups)>,
+        // Invulnerable entities still stop bullets, they just don't take damage
+        mut targets: Query<(&mut Health, &GlobalTransform), Without<Invulnerable>>,
+        hittable: Query<(
+            Option<&Faction>,
+            Option<&PassableFrom>,
+            Option<&Wall>,
+            Option<&Dash>,
+            Option<&SurfaceMaterial>,
+        )>,
+        enemies: Res<EnemyGrid>,
+        friendly_fire: Res<FriendlyFire>,
+        swept: Res<SweptCollision>,
         rapier: Res<RapierContext>,
-        time: Res<Time>,
-        effects: Res<BulletEffects>,
+        fixed: Res<FixedStep>,
+        // Missing until `setup_bullet_trail` has run, until then bullets don't leave particles
+        effects: Option<Res<BulletEffects>>,
+        audio: Res<Audio>,
+        sounds: Res<SoundEffects>,
+        listener: Res<ListenerPos>,
+        mut shake: ResMut<ScreenShake>,
+        mut damaged: EventWriter<DamageDealt>,
+        mut rng: ResMut<GameRng>,
     ) {
-        for (entity, mut tf, mut bullet) in &mut bullets {
-            if let Some((_, intersection)) = rapier.cast_ray_and_get_normal(
+        for (entity, mut tf, mut bullet, &groups) in &mut bullets {
+            bullet.dir.y -= bullet.gravity * fixed.delta_seconds();
+            bullet.steer(&enemies, tf.translation.xy(), fixed.delta_seconds());
+            // Keeps the trail streaming out behind the bullet as gravity and homing turn it
+            tf.rotation = facing(bullet.dir);
+
+            let faction = bullet.faction;
+            let velocity = bullet.dir;
+            let pierced = bullet.pierced.clone();
+            let can_hit = |target| {
+                if pierced.contains(&target) {
+                    return false;
+                }
+                let Ok((other, passable, ..)) = hittable.get(target) else { return true };
+                let friendly = other.map_or(false, |&other| other == faction);
+                let passes = passable.map_or(false, |passable| passable.lets_through(velocity));
+                (**friendly_fire || !friendly) && !passes
+            };
+            let filter = QueryFilter::new()
+                .exclude_collider(bullet.owner)
+                .groups(groups)
+                .predicate(&can_hit);
+
+            let impact = bullet.cast(
+                &rapier,
                 tf.translation.xy(),
-                bullet.dir,
-                bullet.dir.length() * time.delta_seconds() / SPEED,
-                true,
-                QueryFilter::default(),
-            ) {
-                let debris_dir = bullet.dir.normalize()
-                    - 2.0 * bullet.dir.normalize().dot(intersection.normal) * intersection.normal;
-                commands.spawn((
-                    Name::new("Debris particles"),
-                    SpatialBundle {
-                        transform: Transform {
-                            translation: intersection.point.extend(0.0),
-                            rotation: Quat::from_rotation_z(
-                                debris_dir.y.atan2(debris_dir.x) - PI / 2.0,
-                            ),
+                fixed.delta_seconds(),
+                filter,
+                **swept,
+            );
+            let hit_wall = impact.as_ref().map_or(false, |impact| {
+                matches!(hittable.get(impact.entity), Ok((_, _, Some(_), ..)))
+            });
+            let bounce = impact
+                .as_ref()
+                .filter(|_| hit_wall && bullet.bounces > 0)
+                .and_then(|impact| ricochet(bullet.dir, impact.normal));
+            let hit_dash = impact.as_ref().map_or(false, |impact| {
+                let dash = hittable
+                    .get(impact.entity)
+                    .ok()
+                    .and_then(|(.., dash, _)| dash);
+                dash.map_or(false, Dash::is_active)
+            });
+            let deflect = hit_dash && bullet.faction == Faction::Enemy;
+
+            if let (Some(impact), Some((dir, away))) = (impact.as_ref(), bounce) {
+                bullet.bounces -= 1;
+                bullet.dir = BOUNCE_DAMPING * dir;
+                let pos = impact.point + (bullet.radius + BOUNCE_OFFSET) * away;
+                tf.translation = pos.extend(tf.translation.z);
+                bullet.age += fixed.delta_seconds();
+                sounds.play_at(&audio, &sounds.impact, 0.2, impact.point, &listener);
+            } else if let Some(impact) = impact.as_ref().filter(|_| deflect) {
+                // Dashing through enemy fire sends it back at whoever fired it
+                let speed = bullet.dir.length();
+                let back = enemies
+                    .position(bullet.owner)
+                    .and_then(|owner| (owner - impact.point).try_normalize())
+                    .unwrap_or(-bullet.dir.normalize_or_zero());
+                bullet.dir = speed * back;
+                bullet.owner = impact.entity;
+                bullet.faction = Faction::Player;
+                tf.translation = impact.point.extend(tf.translation.z);
+                bullet.age += fixed.delta_seconds();
+                sounds.play_at(&audio, &sounds.impact, 0.4, impact.point, &listener);
+            } else if let Some(impact) = impact.as_ref().filter(|_| bullet.fused) {
+                // Fused bullets don't go off on impact, they come to rest against whatever they
+                // hit and wait for the fuse to burn down
+                bullet.dir = Vec2::ZERO;
+                bullet.gravity = 0.0;
+                let away = impact.normal.normalize_or_zero();
+                let pos = impact.point + (bullet.radius + BOUNCE_OFFSET) * away;
+                tf.translation = pos.extend(tf.translation.z);
+                bullet.age += fixed.delta_seconds();
+                sounds.play_at(&audio, &sounds.impact, 0.2, impact.point, &listener);
+            } else if let Some(impact) = impact {
+                // Passing through whatever it damages, explosive bullets always go off instead
+                let pierce =
+                    bullet.pierce > 0 && bullet.explosive <= 0.0 && targets.contains(impact.entity);
+                let (damage, crit) = bullet.roll_damage(&mut **rng);
+                if bullet.explosive > 0.0 {
+                    let explosion = Explosion {
+                        pos: impact.point,
+                        radius: bullet.explosive,
+                        damage,
+                        crit,
+                        stun: bullet.stun,
+                    };
+                    explosion.explode(
+                        &mut commands,
+                        &rapier,
+                        filter,
+                        &mut targets,
+                        effects.as_deref(),
+                        &mut damaged,
+                    );
+                    shake.add_trauma(explosion.radius / EXPLOSION_TRAUMA_RADIUS);
+                } else if let Ok((mut health, _)) = targets.get_mut(impact.entity) {
+                    health.damage(damage);
+                    damaged.send(DamageDealt {
+                        target: impact.entity,
+                        point: impact.point,
+                        amount: damage,
+                        crit,
+                    });
+                }
+                sounds.play_at(&audio, &sounds.impact, 0.5, impact.point, &listener);
+                if hit_wall {
+                    spawn_decal(&mut commands, impact.point, impact.normal);
+                }
+
+                if let Some(effects) = &effects {
+                    let material = hittable
+                        .get(impact.entity)
+                        .ok()
+                        .and_then(|(.., material)| material.copied());
+                    let debris_dir = reflect(bullet.dir.normalize(), impact.normal);
+                    commands.spawn((
+                        Name::new("Debris particles"),
+                        GameplayEntity,
+                        SpatialBundle {
+                            transform: Transform {
+                                translation: impact.point.extend(0.0),
+                                rotation: Quat::from_rotation_z(
+                                    debris_dir.y.atan2(debris_dir.x) - PI / 2.0,
+                                ),
+                                ..default()
+                            },
                             ..default()
                         },
-                        ..default()
-                    },
-                    ParticleEffect::new(effects.debris.clone()).with_z_layer_2d(Some(0.2)),
-                    Lifetime(5.0),
-                ));
-                commands.entity(entity).despawn();
+                        ParticleEffect::new(effects.debris(material).clone())
+                            .with_z_layer_2d(Some(0.2)),
+                        Lifetime(5.0),
+                    ));
+                    if pierce {
+                        let exit = exit_point(&rapier, impact.entity, impact.point, bullet.dir);
+                        spawn_exit_sparks(&mut commands, effects, exit, bullet.dir);
+                    }
+                }
+
+                if pierce {
+                    bullet.pierce -= 1;
+                    bullet.pierced.push(impact.entity);
+                    tf.translation = impact.point.extend(tf.translation.z);
+                    bullet.age += fixed.delta_seconds();
+                } else {
+                    commands.entity(entity).despawn();
+                    continue;
+                }
             } else {
-                tf.translation += bullet.dir.extend(0.0) * time.delta_seconds();
-                bullet.lifetime -= time.delta_seconds();
+                let step = bullet.dir * fixed.delta_seconds();
+                tf.translation += step.extend(0.0);
+                bullet.traveled += step.length();
+                bullet.age += fixed.delta_seconds();
+            }
+
+            if bullet.fused && bullet.out_of_range() {
+                let (damage, crit) = bullet.roll_damage(&mut **rng);
+                let explosion = Explosion {
+                    pos: tf.translation.xy(),
+                    radius: bullet.explosive,
+                    damage,
+                    crit,
+                    stun: bullet.stun,
+                };
+                explosion.explode(
+                    &mut commands,
+                    &rapier,
+                    filter,
+                    &mut targets,
+                    effects.as_deref(),
+                    &mut damaged,
+                );
+                shake.add_trauma(explosion.radius / EXPLOSION_TRAUMA_RADIUS);
+                commands.entity(entity).despawn();
             }
         }
     }
 
-    fn cleanup(mut commands: Commands, bullets: Query<(Entity, &Bullet)>) {
-        for (entity, bullet) in &bullets {
-            if bullet.lifetime <= 0.0 {
+    /// Despawns bullets that have flown out of range or left the arena. Fused bullets explode in
+    /// [`Bullet::move_bullet`] instead once they're out of range.
+    fn cleanup(
+        mut commands: Commands,
+        bullets: Query<(Entity, &Bullet, &Transform)>,
+        bounds: Res<ArenaBounds>,
+    ) {
+        for (entity, bullet, tf) in &bullets {
+            let spent = bullet.out_of_range() && !bullet.fused;
+            if spent || !bounds.contains(tf.translation.xy()) {
                 commands.entity(entity).despawn();
             }
         }
     }
 }
 
+/// Furthest a bullet can have to travel through something it pierces, to find where it comes
+/// out the other side.
+const MAX_PIERCE_DEPTH: f32 = 500.0;
+/// How far out of the far side of a pierced target the exit sparks start.
+const EXIT_SPARKS_OFFSET: f32 = 2.0;
+
+/// Where a bullet that hit `target` at `entry` flying along `dir` comes out the other side of
+/// it. Without a collider to pass through that's right where it went in.
+fn exit_point(rapier: &RapierContext, target: Entity, entry: Vec2, dir: Vec2) -> Vec2 {
+    let dir = dir.normalize_or_zero();
+    let only_target = |entity: Entity| entity == target;
+    // Coming back from beyond the target, the first thing a ray hits is its far side
+    let beyond = entry + MAX_PIERCE_DEPTH * dir;
+    rapier
+        .cast_ray(
+            beyond,
+            -dir,
+            MAX_PIERCE_DEPTH,
+            true,
+            QueryFilter::new().predicate(&only_target),
+        )
+        .map_or(entry, |(_, toi)| beyond - toi * dir)
+}
+
+/// Sprays a smaller burst of debris out of the far side of a pierced target, along the way the
+/// bullet carries on flying.
+fn spawn_exit_sparks(commands: &mut Commands, effects: &BulletEffects, exit: Vec2, dir: Vec2) {
+    let dir = dir.normalize_or_zero();
+    commands.spawn((
+        Name::new("Exit sparks"),
+        GameplayEntity,
+        SpatialBundle {
+            transform: Transform {
+                translation: (exit + EXIT_SPARKS_OFFSET * dir).extend(0.0),
+                rotation: Quat::from_rotation_z(dir.y.atan2(dir.x) - PI / 2.0),
+                ..default()
+            },
+            ..default()
+        },
+        ParticleEffect::new(effects.exit_debris.clone()).with_z_layer_2d(Some(0.2)),
+        Lifetime(5.0),
+    ));
+}
+
+/// A bullet hole left on a wall.
+#[derive(Component)]
+struct Decal;
+
+/// Leaves a bullet hole at `point` on a surface facing `normal`, which fades away over time.
+fn spawn_decal(commands: &mut Commands, point: Vec2, normal: Vec2) {
+    commands.spawn((
+        Name::new("Bullet hole"),
+        Decal,
+        GameplayEntity,
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(0.1, 0.1, 0.1, DECAL_ALPHA),
+                // Flattened along the surface
+                custom_size: Some(Vec2::new(3.0, 6.0)),
+                ..default()
+            },
+            transform: Transform {
+                // Just above walls
+                translation: point.extend(0.05),
+                rotation: Quat::from_rotation_z(normal.y.atan2(normal.x)),
+                ..default()
+            },
+            ..default()
+        },
+        Lifetime(DECAL_LIFETIME),
+        FadeOut {
+            duration: DECAL_LIFETIME,
+            alpha: DECAL_ALPHA,
+        },
+    ));
+}
+
+/// Removes the oldest bullet holes once there are too many.
+fn limit_decals(mut commands: Commands, decals: Query<(Entity, &Lifetime), With<Decal>>) {
+    let excess = decals.iter().count().saturating_sub(MAX_DECALS);
+    if excess == 0 {
+        return;
+    }
+    let mut decals: Vec<_> = decals.iter().collect();
+    decals.sort_by(|(_, a), (_, b)| a.0.total_cmp(&b.0));
+    for (entity, _) in decals.into_iter().take(excess) {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Explosions this big shake the screen as much as possible.
+const EXPLOSION_TRAUMA_RADIUS: f32 = 200.0;
+/// Radius the explosion particle effect is designed for, it's scaled to match other radii.
+const EXPLOSION_EFFECT_RADIUS: f32 = 100.0;
+
+struct Explosion {
+    pos: Vec2,
+    radius: f32,
+    damage: f32,
+    crit: bool,
+    /// Seconds everything in range is stunned for instead of being damaged, if not zero.
+    stun: f32,
+}
+
+impl Explosion {
+    /// Damages everything with `Health` in range that isn't invulnerable, less the further it is
+    /// from the center. Stunning explosions stun it all for the same time instead.
+    fn explode(
+        &self,
+        commands: &mut Commands,
+        rapier: &RapierContext,
+        filter: QueryFilter,
+        targets: &mut Query<(&mut Health, &GlobalTransform), Without<Invulnerable>>,
+        effects: Option<&BulletEffects>,
+        damaged: &mut EventWriter<DamageDealt>,
+    ) {
+        let mut hit = Vec::new();
+        rapier.intersections_with_shape(
+            self.pos,
+            0.0,
+            &Collider::ball(self.radius),
+            filter,
+            |entity| {
+                hit.push(entity);
+                true
+            },
+        );
+
+        for entity in hit {
+            let Ok((mut health, tf)) = targets.get_mut(entity) else { continue };
+            if self.stun > 0.0 {
+                commands
+                    .entity(entity)
+                    .insert(Stunned::from_seconds(self.stun));
+                continue;
+            }
+            let falloff = 1.0 - tf.translation().xy().distance(self.pos) / self.radius;
+            let amount = self.damage * falloff.clamp(0.0, 1.0);
+            health.damage(amount);
+            damaged.send(DamageDealt {
+                target: entity,
+                point: tf.translation().xy(),
+                amount,
+                crit: self.crit,
+            });
+        }
+
+        let Some(effects) = effects else { return };
+        commands.spawn((
+            Name::new("Explosion particles"),
+            GameplayEntity,
+            SpatialBundle::from_transform(
+                Transform::from_translation(self.pos.extend(0.0))
+                    .with_scale(Vec3::splat(self.radius / EXPLOSION_EFFECT_RADIUS)),
+            ),
+            ParticleEffect::new(effects.explosion.clone()).with_z_layer_2d(Some(0.3)),
+            Lifetime(5.0),
+        ));
+    }
+}
+
+/// Despawns the entity once this many seconds have passed.
 #[derive(Reflect, Component)]
-struct Lifetime(f32);
+pub struct Lifetime(pub f32);
 
 fn despawn_after_lifetime(
     mut commands: Commands,
     mut lifetimes: Query<(Entity, &mut Lifetime)>,
-    time: Res<Time>,
+    time: Res<GameTime>,
 ) {
     for (entity, mut lifetime) in &mut lifetimes {
         lifetime.0 -= time.delta_seconds();
@@ -114,73 +905,269 @@ fn despawn_after_lifetime(
     }
 }
 
+/// Makes a sprite, sprite sheet or colored mesh with a [`Lifetime`] fade away from `alpha` over
+/// the last `duration` seconds of it.
+#[derive(Component)]
+pub struct FadeOut {
+    pub duration: f32,
+    pub alpha: f32,
+}
+
+fn fade_out(
+    mut fading: Query<(
+        &FadeOut,
+        &Lifetime,
+        Option<&mut Sprite>,
+        Option<&mut TextureAtlasSprite>,
+        Option<&Handle<ColorMaterial>>,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (fade, lifetime, sprite, atlas_sprite, material) in &mut fading {
+        let alpha = fade.alpha * (lifetime.0 / fade.duration).clamp(0.0, 1.0);
+        if let Some(mut sprite) = sprite {
+            sprite.color.set_a(alpha);
+        }
+        if let Some(mut sprite) = atlas_sprite {
+            sprite.color.set_a(alpha);
+        }
+        if let Some(material) = material.and_then(|handle| materials.get_mut(handle)) {
+            material.color.set_a(alpha);
+        }
+    }
+}
+
 #[derive(Resource, Debug, Clone)]
 pub struct BulletEffects {
     pub trail: Handle<EffectAsset>,
+    /// Sprays off surfaces that aren't made of any [`SurfaceMaterial`] in particular.
     pub debris: Handle<EffectAsset>,
+    pub metal_debris: Handle<EffectAsset>,
+    pub wood_debris: Handle<EffectAsset>,
+    pub stone_debris: Handle<EffectAsset>,
+    /// Sprayed out of the far side of whatever a piercing bullet passes through.
+    pub exit_debris: Handle<EffectAsset>,
+    pub explosion: Handle<EffectAsset>,
+}
+
+impl BulletEffects {
+    /// Every effect with the file it's loaded from.
+    fn files(&self) -> [(&'static str, &Handle<EffectAsset>); 7] {
+        [
+            (TRAIL_EFFECT_PATH, &self.trail),
+            (DEBRIS_EFFECT_PATH, &self.debris),
+            (METAL_DEBRIS_EFFECT_PATH, &self.metal_debris),
+            (WOOD_DEBRIS_EFFECT_PATH, &self.wood_debris),
+            (STONE_DEBRIS_EFFECT_PATH, &self.stone_debris),
+            (EXIT_DEBRIS_EFFECT_PATH, &self.exit_debris),
+            (EXPLOSION_EFFECT_PATH, &self.explosion),
+        ]
+    }
+
+    /// The debris sprayed off a surface made of `material`.
+    pub fn debris(&self, material: Option<SurfaceMaterial>) -> &Handle<EffectAsset> {
+        match material {
+            None => &self.debris,
+            Some(SurfaceMaterial::Metal) => &self.metal_debris,
+            Some(SurfaceMaterial::Wood) => &self.wood_debris,
+            Some(SurfaceMaterial::Stone) => &self.stone_debris,
+        }
+    }
+}
+
+fn setup_bullet_trail(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    quality: Res<GraphicsQuality>,
+) {
+    let quality = *quality;
+    commands.insert_resource(BulletEffects {
+        trail: effects.add(load_effect(TRAIL_EFFECT_PATH, || trail_effect(quality))),
+        debris: effects.add(load_effect(DEBRIS_EFFECT_PATH, || {
+            debris_effect(quality, None)
+        })),
+        metal_debris: effects.add(load_effect(METAL_DEBRIS_EFFECT_PATH, || {
+            debris_effect(quality, Some(SurfaceMaterial::Metal))
+        })),
+        wood_debris: effects.add(load_effect(WOOD_DEBRIS_EFFECT_PATH, || {
+            debris_effect(quality, Some(SurfaceMaterial::Wood))
+        })),
+        stone_debris: effects.add(load_effect(STONE_DEBRIS_EFFECT_PATH, || {
+            debris_effect(quality, Some(SurfaceMaterial::Stone))
+        })),
+        exit_debris: effects.add(load_effect(EXIT_DEBRIS_EFFECT_PATH, || {
+            exit_debris_effect(quality)
+        })),
+        explosion: effects.add(load_effect(EXPLOSION_EFFECT_PATH, || {
+            explosion_effect(quality)
+        })),
+    });
+}
+
+/// Reads an effect from `path`, falling back to the built-in one if it's missing or invalid.
+/// Effects from files are used as they are, whatever the [`GraphicsQuality`].
+fn load_effect(path: &str, built_in: impl FnOnce() -> EffectAsset) -> EffectAsset {
+    load_ron_or(path, "built-in effect", built_in)
+}
+
+/// Polls the effect files' modification times and swaps in an effect when its file changes. A
+/// file that can't be parsed leaves the current effect in place.
+fn reload_bullet_effects(
+    bullet_effects: Option<Res<BulletEffects>>,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    time: Res<Time>,
+    mut watcher: Local<FileWatcher>,
+) {
+    if !watcher.due(time.delta_seconds(), EFFECT_RELOAD_INTERVAL) {
+        return;
+    }
+    let Some(bullet_effects) = bullet_effects else { return };
+
+    for (path, handle) in bullet_effects.files() {
+        if !watcher.changed(path) {
+            continue;
+        }
+
+        match read_ron::<EffectAsset>(path) {
+            Ok(reloaded) => {
+                info!("Reloaded {path}");
+                if let Some(effect) = effects.get_mut(handle) {
+                    *effect = reloaded;
+                }
+            }
+            Err(err) => error!("Couldn't reload {path}: {err}"),
+        }
+    }
 }
 
-fn setup_bullet_trail(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
+fn trail_effect(quality: GraphicsQuality) -> EffectAsset {
     let mut gradient = Gradient::new();
     gradient.add_key(0.0, Vec4::new(0.5, 0.5, 1.0, 1.0));
     gradient.add_key(1.0, Vec4::new(0.5, 0.5, 1.0, 0.0));
 
-    let spawner = Spawner::rate(300.0.into());
-    let trail = effects.add(
-        EffectAsset {
-            name: "Bullet trail".into(),
-            capacity: 4096,
-            spawner,
-            ..default()
-        }
-        .init(InitPositionCircleModifier {
-            radius: 3.0,
-            dimension: ShapeDimension::Surface,
-            ..default()
-        })
-        .init(InitVelocityCircleModifier {
-            speed: 1.0.into(),
-            ..default()
-        })
-        .init(InitLifetimeModifier {
-            lifetime: Value::Single(0.2),
-        })
-        .render(SizeOverLifetimeModifier {
-            gradient: Gradient::constant(Vec2::splat(1.0)),
-        })
-        .render(ColorOverLifetimeModifier { gradient }),
-    );
+    let spawner = Spawner::rate(quality.particles(300.0).into());
+    EffectAsset {
+        name: "Bullet trail".into(),
+        capacity: quality.capacity(4096),
+        spawner,
+        ..default()
+    }
+    .init(InitPositionCircleModifier {
+        radius: 3.0,
+        dimension: ShapeDimension::Surface,
+        ..default()
+    })
+    .init(TrailVelocityModifier {
+        speed: Value::Uniform((10.0, 40.0)),
+    })
+    .init(InitLifetimeModifier {
+        lifetime: Value::Single(0.2),
+    })
+    .render(SizeOverLifetimeModifier {
+        gradient: Gradient::constant(Vec2::splat(1.0)),
+    })
+    .render(ColorOverLifetimeModifier { gradient })
+}
 
+/// Debris sprayed off a surface made of `material`: bright sparks off metal, brown chunks off
+/// wood, gray grit off stone, and white specks off anything else.
+fn debris_effect(quality: GraphicsQuality, material: Option<SurfaceMaterial>) -> EffectAsset {
+    let (name, color, count, speed, size) = match material {
+        None => (
+            "Debris",
+            Vec3::new(1.0, 1.0, 1.0),
+            20.0,
+            (100.0, 500.0),
+            1.0,
+        ),
+        Some(SurfaceMaterial::Metal) => (
+            "Metal sparks",
+            Vec3::new(1.0, 0.85, 0.4),
+            35.0,
+            (200.0, 700.0),
+            1.0,
+        ),
+        Some(SurfaceMaterial::Wood) => (
+            "Wood chips",
+            Vec3::new(0.55, 0.35, 0.15),
+            12.0,
+            (80.0, 300.0),
+            3.0,
+        ),
+        Some(SurfaceMaterial::Stone) => (
+            "Stone grit",
+            Vec3::new(0.6, 0.6, 0.6),
+            25.0,
+            (100.0, 400.0),
+            2.0,
+        ),
+    };
     let mut gradient = Gradient::new();
-    gradient.add_key(0.0, Vec4::new(1.0, 1.0, 1.0, 1.0));
-    gradient.add_key(1.0, Vec4::new(1.0, 1.0, 1.0, 0.0));
+    gradient.add_key(0.0, color.extend(1.0));
+    gradient.add_key(1.0, color.extend(0.0));
 
-    let spawner = Spawner::once(20.0.into(), true);
+    let spawner = Spawner::once(quality.particles(count).into(), true);
+    EffectAsset {
+        name: name.into(),
+        capacity: quality.capacity(4096),
+        spawner,
+        ..default()
+    }
+    .init(MyPositionCone3dModifier {
+        height: 100.0,
+        base_radius: 50.0,
+        top_radius: 0.0,
+        speed: Value::Uniform(speed),
+        dimension: ShapeDimension::Surface,
+        ..default()
+    })
+    .init(InitLifetimeModifier {
+        lifetime: Value::Single(0.2),
+    })
+    .render(SizeOverLifetimeModifier {
+        gradient: Gradient::constant(Vec2::splat(size)),
+    })
+    .render(ColorOverLifetimeModifier { gradient })
+}
 
-    let debris = effects.add(
-        EffectAsset {
-            name: "Debris".into(),
-            capacity: 4096,
-            spawner,
-            ..default()
-        }
-        .init(MyPositionCone3dModifier {
-            height: 100.0,
-            base_radius: 50.0,
-            top_radius: 0.0,
-            speed: Value::Uniform((100.0, 500.0)),
-            dimension: ShapeDimension::Surface,
-            ..default()
-        })
-        .init(InitLifetimeModifier {
-            lifetime: Value::Single(0.2),
-        })
-        .render(SizeOverLifetimeModifier {
-            gradient: Gradient::constant(Vec2::splat(1.0)),
-        })
-        .render(ColorOverLifetimeModifier { gradient }),
-    );
-    commands.insert_resource(BulletEffects { trail, debris });
+/// The plain debris effect with a smaller burst, so the exit wound doesn't outshine the hit.
+fn exit_debris_effect(quality: GraphicsQuality) -> EffectAsset {
+    EffectAsset {
+        name: "Exit debris".into(),
+        spawner: Spawner::once(quality.particles(8.0).into(), true),
+        ..debris_effect(quality, None)
+    }
+}
+
+fn explosion_effect(quality: GraphicsQuality) -> EffectAsset {
+    let mut gradient = Gradient::new();
+    gradient.add_key(0.0, Vec4::new(1.0, 0.9, 0.3, 1.0));
+    gradient.add_key(0.5, Vec4::new(1.0, 0.4, 0.1, 0.8));
+    gradient.add_key(1.0, Vec4::new(0.3, 0.3, 0.3, 0.0));
+
+    let spawner = Spawner::once(quality.particles(200.0).into(), true);
+    EffectAsset {
+        name: "Explosion".into(),
+        capacity: quality.capacity(4096),
+        spawner,
+        ..default()
+    }
+    .init(InitPositionCircleModifier {
+        radius: EXPLOSION_EFFECT_RADIUS / 4.0,
+        dimension: ShapeDimension::Volume,
+        ..default()
+    })
+    .init(InitVelocityCircleModifier {
+        speed: Value::Uniform((100.0, 400.0)),
+        ..default()
+    })
+    .init(InitLifetimeModifier {
+        lifetime: Value::Uniform((0.2, 0.5)),
+    })
+    .render(SizeOverLifetimeModifier {
+        gradient: Gradient::constant(Vec2::splat(4.0)),
+    })
+    .render(ColorOverLifetimeModifier { gradient })
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
@@ -274,3 +1261,492 @@ impl InitModifier for MyPositionCone3dModifier {
         context.init_code += "init_position_cone3d(transform, &particle);\n";
     }
 }
+
+/// Emits particles backwards along the emitter's x axis, so a trail streams out behind a bullet
+/// facing the way it flies.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Reflect, FromReflect, Serialize, Deserialize)]
+pub struct TrailVelocityModifier {
+    /// The speed of the particles on spawn.
+    pub speed: Value<f32>,
+}
+
+#[typetag::serde]
+impl Modifier for TrailVelocityModifier {
+    fn context(&self) -> ModifierContext {
+        ModifierContext::Init
+    }
+
+    fn as_init(&self) -> Option<&dyn InitModifier> {
+        Some(self)
+    }
+
+    fn as_init_mut(&mut self) -> Option<&mut dyn InitModifier> {
+        Some(self)
+    }
+
+    fn attributes(&self) -> &[&'static Attribute] {
+        &[Attribute::VELOCITY]
+    }
+
+    fn boxed_clone(&self) -> BoxedModifier {
+        Box::new(*self)
+    }
+}
+
+#[typetag::serde]
+impl InitModifier for TrailVelocityModifier {
+    fn apply(&self, context: &mut InitContext) {
+        context.init_extra += &format!(
+            r##"fn init_trail_velocity(transform: mat4x4<f32>, particle: ptr<function, Particle>) {{
+    let dir = normalize((transform * vec4<f32>(-1.0, 0.0, 0.0, 0.0)).xyz);
+    (*particle).{0} = dir * {1};
+}}
+"##,
+            Attribute::VELOCITY.name(),
+            self.speed.to_wgsl_string(),
+        );
+
+        context.init_code += "init_trail_velocity(transform, &particle);\n";
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+    use crate::headless::{headless_app, run_fixed_steps};
+
+    /// Adds Rapier to `app` and registers the colliders spawned so far, so bullets can hit them.
+    fn with_physics(app: &mut App) {
+        app.add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0));
+        // Game time doesn't advance, so this only syncs the colliders without running a step
+        app.update();
+    }
+
+    /// Spawns a wall of the given size centered on `pos`, turned by `angle`.
+    fn spawn_test_wall(app: &mut App, pos: Vec2, size: Vec2, angle: f32) -> Entity {
+        let tf = Transform::from_translation(pos.extend(0.0))
+            .with_rotation(Quat::from_rotation_z(angle));
+        app.world
+            .spawn((
+                Wall,
+                Collider::cuboid(size.x / 2.0, size.y / 2.0),
+                TransformBundle::from_transform(tf),
+            ))
+            .id()
+    }
+
+    /// Fires a bullet from `pos` in `dir` with the system that moves and despawns bullets added.
+    fn bullet_app(pos: Vec2, dir: Vec2, stats: BulletStats) -> (App, Entity) {
+        fired_by(Vec2::ZERO, 0.0, pos, dir, stats)
+    }
+
+    /// Like [`bullet_app`], with the bullet fired by a shooter at `shooter_pos` with a collider of
+    /// `shooter_radius`.
+    fn fired_by(
+        shooter_pos: Vec2,
+        shooter_radius: f32,
+        pos: Vec2,
+        dir: Vec2,
+        stats: BulletStats,
+    ) -> (App, Entity) {
+        let mut app = headless_app();
+        app.add_system_to_stage(FixedUpdateStage, Bullet::move_bullet.label(Simulation))
+            .add_system(Bullet::cleanup);
+
+        let owner = app.world.spawn_empty().id();
+        let trail = app.world.resource::<BulletEffects>().trail.clone();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        Bullet::spawn(
+            &mut commands,
+            pos.extend(0.0),
+            dir,
+            stats,
+            Shooter {
+                entity: owner,
+                faction: Faction::Player,
+                velocity: Vec2::ZERO,
+                pos: shooter_pos,
+                radius: shooter_radius,
+            },
+            Some(trail),
+        );
+        queue.apply(&mut app.world);
+
+        let bullet = app
+            .world
+            .query_filtered::<Entity, With<Bullet>>()
+            .single(&app.world);
+        (app, bullet)
+    }
+
+    #[test]
+    fn lifetime_runs_out() {
+        let mut app = headless_app();
+        app.add_system(despawn_after_lifetime);
+        let entity = app.world.spawn(Lifetime(0.5)).id();
+
+        run_fixed_steps(&mut app, 29);
+        assert!(app.world.get::<Lifetime>(entity).unwrap().0 > 0.0);
+        run_fixed_steps(&mut app, 2);
+        assert!(app.world.get_entity(entity).is_none());
+    }
+
+    #[test]
+    fn bullet_travels_at_its_speed() {
+        let dir = Vec2::new(3.0, 4.0);
+        let (mut app, bullet) = bullet_app(Vec2::new(-100.0, -100.0), dir, default());
+
+        run_fixed_steps(&mut app, 30);
+        // Half a second at 60 steps per second
+        let speed = Tuning::default().bullet_speed;
+        let expected = Vec2::new(-100.0, -100.0) + dir.normalize() * speed * 0.5;
+        let pos = app.world.get::<Interpolated>(bullet).unwrap().current.xy();
+        assert!(pos.abs_diff_eq(expected, 0.01), "{pos} != {expected}");
+        let traveled = app.world.get::<Bullet>(bullet).unwrap().traveled;
+        assert!((traveled - speed * 0.5).abs() < 0.01, "{traveled}");
+    }
+
+    #[test]
+    fn bullet_carries_on_from_where_it_was_paused() {
+        let (mut app, bullet) = bullet_app(Vec2::new(-1000.0, 0.0), Vec2::X, default());
+        let pos = |app: &App| app.world.get::<Interpolated>(bullet).unwrap().current.xy();
+        run_fixed_steps(&mut app, 10);
+        let paused_at = pos(&app);
+
+        let mut state = app.world.resource_mut::<State<GameState>>();
+        state.push(GameState::Paused).unwrap();
+        // Long enough for it to have flown out of range if it kept going
+        run_fixed_steps(&mut app, 120);
+        assert_eq!(pos(&app), paused_at);
+
+        let mut state = app.world.resource_mut::<State<GameState>>();
+        state.pop().unwrap();
+        run_fixed_steps(&mut app, 1);
+        let step = Tuning::default().bullet_speed / 60.0;
+        let moved = pos(&app) - paused_at;
+        assert!(moved.abs_diff_eq(Vec2::X * step, 0.01), "{moved}");
+    }
+
+    #[test]
+    fn bullet_bounces_off_a_wall() {
+        let dir = Vec2::new(3.0, -4.0);
+        let (bounced, away) = ricochet(dir, Vec2::Y).unwrap();
+        assert!(bounced.abs_diff_eq(Vec2::new(3.0, 4.0), 1e-5), "{bounced}");
+        assert_eq!(away, Vec2::Y);
+    }
+
+    #[test]
+    fn bullet_flies_back_out_of_a_corner() {
+        // Into the corner between a wall to the right and one above
+        let dir = Vec2::new(1.0, 1.0);
+        let (bounced, _) = ricochet(dir, Vec2::new(-1.0, -1.0)).unwrap();
+        assert!(bounced.abs_diff_eq(-dir, 1e-5), "{bounced}");
+        // Right at the corner there may be no normal, or one facing into the other wall
+        for normal in [Vec2::ZERO, Vec2::splat(f32::NAN), Vec2::new(1.0, 0.0)] {
+            let (bounced, away) = ricochet(dir, normal).unwrap();
+            assert_eq!(bounced, -dir, "{normal}");
+            assert!(away.abs_diff_eq(-dir.normalize(), 1e-5), "{normal}");
+        }
+    }
+
+    #[test]
+    fn bullet_fired_into_a_corner_comes_back_out() {
+        for swept in [false, true] {
+            let stats = BulletStats {
+                bounces: 2,
+                ..default()
+            };
+            let (mut app, bullet) = bullet_app(Vec2::ZERO, Vec2::ONE, stats);
+            app.insert_resource(SweptCollision(swept));
+            // Meeting in a corner at (100, 100), a wall to the right and one above
+            spawn_test_wall(&mut app, Vec2::new(110.0, 0.0), Vec2::new(20.0, 400.0), 0.0);
+            spawn_test_wall(&mut app, Vec2::new(0.0, 110.0), Vec2::new(400.0, 20.0), 0.0);
+            with_physics(&mut app);
+
+            // Long enough to reach the corner and fly a good way back, slowed by the bounces
+            run_fixed_steps(&mut app, 20);
+            let state = app.world.get::<Bullet>(bullet).unwrap();
+            let dir = state.velocity().normalize();
+            assert!(
+                dir.abs_diff_eq(-Vec2::ONE.normalize(), 0.01),
+                "{swept}: {dir}"
+            );
+            let pos = app.world.get::<Interpolated>(bullet).unwrap().current.xy();
+            assert!(pos.x < 50.0 && pos.y < 50.0, "{swept}: {pos}");
+        }
+    }
+
+    #[test]
+    fn grazing_bullet_doesnt_bounce() {
+        assert_eq!(ricochet(Vec2::new(1.0, -0.01), Vec2::Y), None);
+    }
+
+    const FALLOFF: DamageFalloff = DamageFalloff {
+        start: 100.0,
+        end: 300.0,
+        min_damage: 4.0,
+    };
+
+    #[test]
+    fn full_damage_before_falloff_starts() {
+        assert_eq!(FALLOFF.damage_at(10.0, 0.0), 10.0);
+        assert_eq!(FALLOFF.damage_at(10.0, 100.0), 10.0);
+    }
+
+    #[test]
+    fn damage_drops_linearly_during_falloff() {
+        assert!((FALLOFF.damage_at(10.0, 200.0) - 7.0).abs() < 1e-5);
+        assert!((FALLOFF.damage_at(10.0, 250.0) - 5.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn damage_stays_at_minimum_after_falloff_ends() {
+        assert_eq!(FALLOFF.damage_at(10.0, 300.0), 4.0);
+        assert_eq!(FALLOFF.damage_at(10.0, 1000.0), 4.0);
+        // A weak bullet isn't made stronger by the minimum
+        assert_eq!(FALLOFF.damage_at(2.0, 1000.0), 2.0);
+    }
+
+    #[test]
+    fn swept_bullet_bounces_off_a_rotated_wall() {
+        let stats = BulletStats {
+            bounces: 1,
+            ..default()
+        };
+        let (mut app, bullet) = bullet_app(Vec2::new(-200.0, 0.0), Vec2::X, stats);
+        app.insert_resource(SweptCollision(true));
+        // Its face towards the bullet points down and to the left
+        spawn_test_wall(&mut app, Vec2::ZERO, Vec2::new(20.0, 400.0), PI / 4.0);
+        with_physics(&mut app);
+
+        run_fixed_steps(&mut app, 30);
+        // Glancing off a wall at 45 degrees turns it straight down
+        let dir = app
+            .world
+            .get::<Bullet>(bullet)
+            .unwrap()
+            .velocity()
+            .normalize();
+        assert!(dir.abs_diff_eq(Vec2::NEG_Y, 0.01), "{dir}");
+    }
+
+    #[test]
+    fn bullet_looks_dont_change_how_it_flies() {
+        let stats = BulletStats {
+            size: 40.0,
+            color: Color::CYAN,
+            ..default()
+        };
+        let (mut app, bullet) = bullet_app(Vec2::new(-100.0, -100.0), Vec2::X, stats);
+
+        let sprite = app.world.get::<Sprite>(bullet).unwrap();
+        assert_eq!(sprite.custom_size, Some(Vec2::splat(40.0)));
+        assert_eq!(sprite.color, Color::CYAN);
+        run_fixed_steps(&mut app, 30);
+        let expected = Vec2::new(-100.0, -100.0) + Vec2::X * stats.speed * 0.5;
+        let pos = app.world.get::<Interpolated>(bullet).unwrap().current.xy();
+        assert!(pos.abs_diff_eq(expected, 0.01), "{pos} != {expected}");
+    }
+
+    #[test]
+    fn bullet_despawns_when_its_lifetime_runs_out() {
+        let stats = BulletStats {
+            range: BulletRange::Time(0.5),
+            ..default()
+        };
+        let (mut app, bullet) = bullet_app(Vec2::new(-1000.0, 0.0), Vec2::X, stats);
+
+        // It's still there on the step its lifetime runs out, and removed before the next one
+        run_fixed_steps(&mut app, 30);
+        assert!(app.world.get_entity(bullet).is_some());
+        run_fixed_steps(&mut app, 1);
+        assert!(app.world.get_entity(bullet).is_none());
+    }
+
+    #[test]
+    fn bullets_fly_before_effects_are_set_up() {
+        let (mut app, bullet) = bullet_app(Vec2::ZERO, Vec2::X, default());
+        // Like on the first frames, before `setup_bullet_trail` has inserted the effects
+        app.world.remove_resource::<BulletEffects>();
+
+        run_fixed_steps(&mut app, 1);
+        assert!(app.world.get::<Interpolated>(bullet).unwrap().current.x > 0.0);
+    }
+
+    #[test]
+    fn bullet_starts_outside_its_shooter() {
+        // Fired from inside the shooter, at an angle to the center
+        let dir = Vec2::new(1.0, 1.0).normalize();
+        let (app, bullet) = fired_by(Vec2::ZERO, 40.0, Vec2::new(10.0, 0.0), dir, default());
+
+        let pos = app.world.get::<Interpolated>(bullet).unwrap().current.xy();
+        let expected = 40.0 + SIZE / 2.0 + SPAWN_CLEARANCE;
+        assert!((pos.length() - expected).abs() < 0.01, "{pos}");
+        // Still on the line it was fired along
+        assert!((pos - Vec2::new(10.0, 0.0))
+            .normalize()
+            .abs_diff_eq(dir, 0.001));
+    }
+
+    #[test]
+    fn bullet_already_clear_of_its_shooter_starts_where_fired() {
+        let pos = Vec2::new(100.0, 0.0);
+        let (app, bullet) = fired_by(Vec2::ZERO, 40.0, pos, Vec2::Y, default());
+
+        let start = app.world.get::<Interpolated>(bullet).unwrap().current.xy();
+        assert_eq!(start, pos);
+    }
+
+    #[test]
+    fn fire_bullet_event_spawns_a_bullet() {
+        let mut app = headless_app();
+        app.add_system(fire_bullets);
+        let owner = app.world.spawn_empty().id();
+        app.world
+            .resource_mut::<Events<FireBullet>>()
+            .send(FireBullet {
+                origin: Vec3::new(10.0, 20.0, 0.0),
+                dir: Vec2::X,
+                stats: default(),
+                shooter: Shooter {
+                    entity: owner,
+                    faction: Faction::Enemy,
+                    velocity: Vec2::ZERO,
+                    pos: Vec2::ZERO,
+                    radius: 0.0,
+                },
+            });
+
+        app.update();
+        let (bullet, tf) = app
+            .world
+            .query::<(&Bullet, &Transform)>()
+            .single(&app.world);
+        assert_eq!(bullet.owner, owner);
+        assert_eq!(bullet.faction, Faction::Enemy);
+        assert_eq!(tf.translation.xy(), Vec2::new(10.0, 20.0));
+    }
+
+    #[test]
+    fn remaining_lifetime_counts_down() {
+        let stats = BulletStats {
+            range: BulletRange::Time(1.0),
+            ..default()
+        };
+        let (mut app, bullet) = bullet_app(Vec2::new(-1000.0, 0.0), Vec2::X, stats);
+        assert_eq!(
+            app.world
+                .get::<Bullet>(bullet)
+                .unwrap()
+                .remaining_lifetime(),
+            1.0
+        );
+
+        run_fixed_steps(&mut app, 30);
+        let remaining = app
+            .world
+            .get::<Bullet>(bullet)
+            .unwrap()
+            .remaining_lifetime();
+        assert!((remaining - 0.5).abs() < 0.001, "{remaining}");
+    }
+
+    #[test]
+    fn reset_bullet_flies_its_full_range_again() {
+        let stats = BulletStats {
+            range: BulletRange::Distance(600.0),
+            speed: 600.0,
+            ..default()
+        };
+        let (mut app, bullet) = bullet_app(Vec2::new(-1000.0, 0.0), Vec2::X, stats);
+
+        run_fixed_steps(&mut app, 45);
+        app.world
+            .get_mut::<Bullet>(bullet)
+            .unwrap()
+            .reset(Vec2::new(0.0, 600.0), BulletRange::Distance(600.0));
+        let reset = app.world.get::<Bullet>(bullet).unwrap();
+        assert_eq!(reset.remaining_lifetime(), 1.0);
+        assert_eq!(reset.velocity(), Vec2::new(0.0, 600.0));
+
+        // Well past when it would have run out without being reset
+        run_fixed_steps(&mut app, 45);
+        assert!(app.world.get_entity(bullet).is_some());
+        run_fixed_steps(&mut app, 20);
+        assert!(app.world.get_entity(bullet).is_none());
+    }
+
+    #[test]
+    fn reset_bullet_can_bounce_and_pierce_again() {
+        let stats = BulletStats {
+            bounces: 2,
+            pierce: 1,
+            ..default()
+        };
+        let (mut app, bullet) = bullet_app(Vec2::ZERO, Vec2::X, stats);
+        let target = app.world.spawn_empty().id();
+
+        let mut state = app.world.get_mut::<Bullet>(bullet).unwrap();
+        state.bounces = 0;
+        state.pierce = 0;
+        state.pierced.push(target);
+        state.reset(Vec2::X * 600.0, BulletRange::Time(1.0));
+        assert_eq!(state.bounces, 2);
+        assert_eq!(state.pierce, 1);
+        assert!(state.pierced.is_empty());
+    }
+
+    #[test]
+    fn fused_bullet_explodes_when_its_fuse_runs_out() {
+        let stats = BulletStats {
+            range: BulletRange::Time(0.5),
+            explosive: 100.0,
+            fused: true,
+            ..default()
+        };
+        let (mut app, bullet) = bullet_app(Vec2::new(-1000.0, 0.0), Vec2::X, stats);
+
+        run_fixed_steps(&mut app, 29);
+        assert!(app.world.get_entity(bullet).is_some());
+        run_fixed_steps(&mut app, 1);
+        assert!(app.world.get_entity(bullet).is_none());
+        let explosions = app
+            .world
+            .query::<&Name>()
+            .iter(&app.world)
+            .filter(|name| name.as_str() == "Explosion particles")
+            .count();
+        assert_eq!(explosions, 1);
+    }
+
+    #[test]
+    fn fused_bullet_only_explodes_once_its_fuse_runs_out() {
+        let stats = BulletStats {
+            range: BulletRange::Time(0.5),
+            explosive: 100.0,
+            fused: true,
+            ..default()
+        };
+        let (mut app, bullet) = bullet_app(Vec2::new(-200.0, 0.0), Vec2::X, stats);
+        let enemy = app
+            .world
+            .spawn((
+                Health::new(100.0),
+                Collider::ball(20.0),
+                TransformBundle::default(),
+            ))
+            .id();
+        with_physics(&mut app);
+
+        // It reaches the enemy after about 8 steps, and stops there without going off
+        run_fixed_steps(&mut app, 25);
+        assert!(app.world.get_entity(bullet).is_some());
+        assert_eq!(app.world.get::<Health>(enemy).unwrap().current, 100.0);
+        run_fixed_steps(&mut app, 5);
+        assert!(app.world.get_entity(bullet).is_none());
+        assert!(app.world.get::<Health>(enemy).unwrap().current < 100.0);
+    }
+}
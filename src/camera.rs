@@ -1,13 +1,255 @@
+use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
+use bevy::render::camera::Viewport;
+use bevy::transform::TransformSystem;
+use leafwing_input_manager::prelude::*;
+use rand::Rng;
+
+use crate::arena::ArenaBounds;
+use crate::fixed::interpolate_positions;
+use crate::mouse::{uses_mouse, HoveredCamera};
+use crate::player::{Action, AimTarget, Player};
+use crate::settings::WindowSettings;
 
 pub struct GameCameraPlugin;
 
 impl Plugin for GameCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_camera);
+        app.init_resource::<ScreenShake>()
+            .init_resource::<CameraFollow>()
+            .add_startup_system(spawn_camera)
+            .add_system(assign_cameras)
+            .add_system(split_screen.after(assign_cameras))
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::new()
+                    .after(interpolate_positions)
+                    .before(TransformSystem::TransformPropagate)
+                    .with_system(follow_player)
+                    .with_system(shake_camera.after(follow_player)),
+            );
+    }
+}
+
+/// Largest distance the camera is offset by at full trauma.
+const MAX_SHAKE_OFFSET: f32 = 30.0;
+/// Largest angle the camera is rotated by at full trauma.
+const MAX_SHAKE_ANGLE: f32 = 0.05;
+/// How much trauma wears off per second.
+const TRAUMA_DECAY: f32 = 1.5;
+/// How far below z = 0 things are still drawn.
+const BACKGROUND_DEPTH: f32 = 100.0;
+/// How quickly the look-ahead offset catches up with where the player is aiming, higher is
+/// snappier.
+const LOOK_AHEAD_SMOOTHING: f32 = 4.0;
+
+/// Shakes the camera proportionally to the square of `trauma`, which decays over time.
+#[derive(Resource, Default)]
+pub struct ScreenShake {
+    trauma: f32,
+}
+
+impl ScreenShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+}
+
+/// How the camera follows the player.
+#[derive(Resource, Debug, Clone)]
+pub struct CameraFollow {
+    /// Size of the area in the middle of the view the player can move around in without the
+    /// camera following.
+    pub deadzone: Vec2,
+    /// How far ahead of the player the camera looks when aiming at the edge of the view.
+    pub look_ahead: f32,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        Self {
+            deadzone: Vec2::new(120.0, 80.0),
+            look_ahead: 150.0,
+        }
     }
 }
 
+/// The point the camera looks at before shake is applied.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct CameraFocus(pub Vec2);
+
+/// A camera following one of the players. With more than one player the window is split
+/// vertically, side by side, between their cameras in order of their priority.
+#[derive(Component, Default)]
+pub struct PlayerCamera {
+    /// `None` while there's no player for this camera, it then stays where it is.
+    pub player: Option<Entity>,
+    look_offset: Vec2,
+}
+
+fn camera_bundle(priority: isize) -> impl Bundle {
+    let mut camera = Camera2dBundle::default();
+    // By default nothing below z = 0 is drawn, this leaves room for background layers
+    camera.projection.near = -BACKGROUND_DEPTH;
+    camera.camera.priority = priority;
+    (
+        Name::new("Camera"),
+        camera,
+        CameraFocus::default(),
+        PlayerCamera::default(),
+        // The HUD is only drawn once, by the first camera
+        UiCameraConfig {
+            show_ui: priority == 0,
+        },
+    )
+}
+
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn((Name::new("Camera"), Camera2dBundle::default()));
+    commands.spawn(camera_bundle(0));
+}
+
+/// Gives every player a camera of their own, reusing cameras of players that are gone. Cameras
+/// beyond the first that aren't needed anymore are removed.
+fn assign_cameras(
+    mut commands: Commands,
+    players: Query<Entity, With<Player>>,
+    mut cameras: Query<(Entity, &mut PlayerCamera, &Camera)>,
+) {
+    for (_, mut camera, _) in &mut cameras {
+        if camera
+            .player
+            .map_or(false, |player| !players.contains(player))
+        {
+            camera.player = None;
+        }
+    }
+
+    let mut priority = cameras
+        .iter()
+        .map(|(_, _, camera)| camera.priority)
+        .max()
+        .unwrap_or(-1);
+    for player in &players {
+        if cameras
+            .iter()
+            .any(|(_, camera, _)| camera.player == Some(player))
+        {
+            continue;
+        }
+        let free = cameras
+            .iter_mut()
+            .find(|(_, camera, _)| camera.player.is_none());
+        if let Some((_, mut camera, _)) = free {
+            camera.player = Some(player);
+        } else {
+            priority += 1;
+            commands
+                .spawn(camera_bundle(priority))
+                .insert(PlayerCamera {
+                    player: Some(player),
+                    ..default()
+                });
+        }
+    }
+
+    let mut total = cameras.iter().count();
+    let wanted = players.iter().count().max(1);
+    for (entity, camera, _) in &cameras {
+        if total > wanted && camera.player.is_none() && camera.priority != 0 {
+            commands.entity(entity).despawn_recursive();
+            total -= 1;
+        }
+    }
+}
+
+/// Splits the window vertically between all cameras, or gives the whole window to the only one.
+fn split_screen(mut cameras: Query<&mut Camera, With<PlayerCamera>>, windows: Res<Windows>) {
+    let Some(window) = windows.get_primary() else { return };
+    let mut cameras: Vec<_> = cameras.iter_mut().collect();
+    cameras.sort_by_key(|camera| camera.priority);
+
+    let count = cameras.len() as u32;
+    let size = UVec2::new(window.physical_width(), window.physical_height());
+    let width = size.x / count.max(1);
+    for (i, mut camera) in cameras.into_iter().enumerate() {
+        let viewport = (count > 1).then(|| Viewport {
+            physical_position: UVec2::new(i as u32 * width, 0),
+            physical_size: UVec2::new(width, size.y),
+            ..default()
+        });
+        if camera.viewport != viewport {
+            camera.viewport = viewport;
+        }
+    }
+}
+
+/// Moves the camera just enough to keep a point slightly ahead of where the player is aiming
+/// inside the deadzone, except near the edges of the arena where the camera stops so it never
+/// shows anything outside of it.
+fn follow_player(
+    mut cameras: Query<(Entity, &Camera, &mut CameraFocus, &mut PlayerCamera)>,
+    players: Query<(&Transform, &AimTarget, &InputMap<Action>), With<Player>>,
+    follow: Res<CameraFollow>,
+    bounds: Res<ArenaBounds>,
+    windows: Res<Windows>,
+    hovered: Res<HoveredCamera>,
+    time: Res<Time>,
+) {
+    let window = windows.get_primary().unwrap();
+    let half_deadzone = follow.deadzone / 2.0;
+    for (entity, camera, mut focus, mut player_camera) in &mut cameras {
+        let Some((player_tf, aim_target, input_map)) = player_camera
+            .player
+            .and_then(|player| players.get(player).ok())
+        else {
+            continue;
+        };
+        let view = camera
+            .logical_viewport_size()
+            .unwrap_or(Vec2::new(window.width(), window.height()));
+        let half_view = view / 2.0;
+
+        // Measured from the center of the view rather than the player, as that moves along with
+        // the camera and would make it keep drifting further ahead. Aiming near the center, or
+        // with the cursor in another player's view, lets the camera ease back onto the player.
+        let cursor_elsewhere =
+            uses_mouse(input_map) && aim_target.on_cursor() && **hovered != Some(entity);
+        let aim = if cursor_elsewhere {
+            Vec2::ZERO
+        } else {
+            ((aim_target.point - **focus) / half_view).clamp_length_max(1.0)
+        };
+        let smoothing = (LOOK_AHEAD_SMOOTHING * time.delta_seconds()).min(1.0);
+        player_camera.look_offset = player_camera
+            .look_offset
+            .lerp(follow.look_ahead * aim, smoothing);
+
+        let target = player_tf.translation.xy() + player_camera.look_offset;
+        let offset = target - **focus;
+        let outside = offset - offset.clamp(-half_deadzone, half_deadzone);
+        **focus = bounds.clamp(**focus + outside, half_view);
+    }
+}
+
+pub fn shake_camera(
+    mut camera: Query<(&mut Transform, &CameraFocus)>,
+    mut shake: ResMut<ScreenShake>,
+    settings: Res<WindowSettings>,
+    time: Res<Time>,
+) {
+    let mut rng = rand::thread_rng();
+    let amount = shake.trauma * shake.trauma;
+    for (mut tf, focus) in &mut camera {
+        let offset = MAX_SHAKE_OFFSET
+            * amount
+            * Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+        tf.translation = (**focus + offset).extend(tf.translation.z);
+        let angle = if settings.shake_rotation {
+            MAX_SHAKE_ANGLE * amount * rng.gen_range(-1.0..1.0)
+        } else {
+            0.0
+        };
+        tf.rotation = Quat::from_rotation_z(angle);
+    }
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.0);
 }
@@ -0,0 +1,40 @@
+use bevy_rapier2d::prelude::*;
+
+pub const PLAYER_GROUP: Group = Group::GROUP_1;
+pub const ENEMY_GROUP: Group = Group::GROUP_2;
+pub const BULLET_GROUP: Group = Group::GROUP_3;
+pub const WALL_GROUP: Group = Group::GROUP_4;
+pub const PICKUP_GROUP: Group = Group::GROUP_5;
+
+pub fn player_groups() -> CollisionGroups {
+    CollisionGroups::new(
+        PLAYER_GROUP,
+        ENEMY_GROUP | BULLET_GROUP | WALL_GROUP | PICKUP_GROUP,
+    )
+}
+
+pub fn enemy_groups() -> CollisionGroups {
+    CollisionGroups::new(
+        ENEMY_GROUP,
+        PLAYER_GROUP | ENEMY_GROUP | BULLET_GROUP | WALL_GROUP,
+    )
+}
+
+/// Turrets stand in the way of enemies and their bullets like the player does.
+pub fn turret_groups() -> CollisionGroups {
+    CollisionGroups::new(PLAYER_GROUP, ENEMY_GROUP | BULLET_GROUP | WALL_GROUP)
+}
+
+/// Bullets don't have colliders, these groups are used to filter their raycasts.
+pub fn bullet_groups() -> CollisionGroups {
+    CollisionGroups::new(BULLET_GROUP, PLAYER_GROUP | ENEMY_GROUP | WALL_GROUP)
+}
+
+/// Pickups can only be picked up by players.
+pub fn pickup_groups() -> CollisionGroups {
+    CollisionGroups::new(PICKUP_GROUP, PLAYER_GROUP)
+}
+
+pub fn wall_groups() -> CollisionGroups {
+    CollisionGroups::new(WALL_GROUP, Group::ALL)
+}
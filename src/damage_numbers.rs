@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+
+use crate::game_time::GameTime;
+use crate::health::DamageDealt;
+use crate::hud::HudFont;
+use crate::state::GameState;
+
+pub struct DamageNumbersPlugin;
+
+impl Plugin for DamageNumbersPlugin {
+    fn build(&self, app: &mut App) {
+        // The HUD font is loaded during `Startup`
+        app.add_startup_system_to_stage(StartupStage::PostStartup, spawn_damage_numbers)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(show_damage_numbers)
+                    .with_system(animate_damage_numbers.after(show_damage_numbers)),
+            );
+    }
+}
+
+/// How many damage numbers can be on screen at once, past this the oldest ones are reused.
+const POOL_SIZE: usize = 64;
+/// Seconds a damage number stays on screen.
+const DURATION: f32 = 1.0;
+/// Pixels per second damage numbers float upwards.
+const RISE_SPEED: f32 = 40.0;
+const FONT_SIZE: f32 = 18.0;
+/// Critical hits are shown this much bigger.
+const CRIT_SCALE: f32 = 1.5;
+const CRIT_COLOR: Color = Color::GOLD;
+
+/// A floating number showing damage dealt. They're spawned once up front and reused, rather
+/// than spawned per hit, so a hail of bullets doesn't spawn and despawn hundreds of entities.
+#[derive(Component)]
+struct DamageNumber {
+    /// Seconds since it was shown, it's unused once this reaches [`DURATION`].
+    age: f32,
+}
+
+impl DamageNumber {
+    fn is_active(&self) -> bool {
+        self.age < DURATION
+    }
+}
+
+fn spawn_damage_numbers(mut commands: Commands, font: Res<HudFont>) {
+    let style = TextStyle {
+        font: font.0.clone(),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+    for _ in 0..POOL_SIZE {
+        commands.spawn((
+            Name::new("Damage number"),
+            DamageNumber { age: DURATION },
+            Text2dBundle {
+                text: Text::from_section("", style.clone()).with_alignment(TextAlignment::CENTER),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            },
+        ));
+    }
+}
+
+fn show_damage_numbers(
+    mut damaged: EventReader<DamageDealt>,
+    mut numbers: Query<(
+        &mut DamageNumber,
+        &mut Text,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    for damage in damaged.iter() {
+        // Prefer an unused number, otherwise take over the one that's been around the longest
+        let Some((mut number, mut text, mut tf, mut visibility)) = numbers
+            .iter_mut()
+            .max_by(|(a, ..), (b, ..)| a.age.total_cmp(&b.age))
+        else {
+            return;
+        };
+
+        let (color, scale) = if damage.crit {
+            (CRIT_COLOR, CRIT_SCALE)
+        } else {
+            (Color::WHITE, 1.0)
+        };
+        number.age = 0.0;
+        text.sections[0].value = format!("{:.0}", damage.amount);
+        text.sections[0].style.color = color;
+        tf.translation = damage.point.extend(1.0);
+        tf.scale = Vec3::splat(scale);
+        visibility.is_visible = true;
+    }
+}
+
+fn animate_damage_numbers(
+    mut numbers: Query<(
+        &mut DamageNumber,
+        &mut Text,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+    time: Res<GameTime>,
+) {
+    for (mut number, mut text, mut tf, mut visibility) in &mut numbers {
+        if !number.is_active() {
+            continue;
+        }
+
+        number.age += time.delta_seconds();
+        if !number.is_active() {
+            visibility.is_visible = false;
+            continue;
+        }
+        tf.translation.y += RISE_SPEED * time.delta_seconds();
+        text.sections[0]
+            .style
+            .color
+            .set_a(1.0 - number.age / DURATION);
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::bullet::Bullet;
+use crate::enemy::{nearest, AiState, Enemy};
+use crate::fixed::FixedStep;
+use crate::player::{MoveDir, Player};
+
+/// Draws bullet velocities, the players' movement and what enemies are chasing, toggled with F3.
+/// F2 toggles Rapier's collider outlines.
+pub struct DebugGizmosPlugin;
+
+impl Plugin for DebugGizmosPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugGizmos>()
+            .add_system(toggle_debug_gizmos)
+            .add_system(toggle_collider_outlines)
+            .add_system(
+                draw_debug_gizmos
+                    .with_run_criteria(gizmos_enabled)
+                    .after(toggle_debug_gizmos),
+            );
+    }
+}
+
+const LINE_WIDTH: f32 = 2.0;
+const LINE_Z: f32 = 5.0;
+/// Bullet velocities are drawn as how far the bullet travels in this many seconds.
+const VELOCITY_SCALE: f32 = 0.05;
+/// The player's movement is drawn as how far they travel in this many seconds.
+const MOVE_SCALE: f32 = 0.5;
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct DebugGizmos(pub bool);
+
+/// One of the sprites used to draw lines, they're kept around and reused between frames.
+#[derive(Component)]
+struct GizmoLine;
+
+fn toggle_debug_gizmos(
+    mut commands: Commands,
+    mut gizmos: ResMut<DebugGizmos>,
+    keys: Res<Input<KeyCode>>,
+    lines: Query<Entity, With<GizmoLine>>,
+) {
+    if !keys.just_pressed(KeyCode::F3) {
+        return;
+    }
+    **gizmos = !**gizmos;
+    if !**gizmos {
+        for entity in &lines {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn toggle_collider_outlines(mut context: ResMut<DebugRenderContext>, keys: Res<Input<KeyCode>>) {
+    if keys.just_pressed(KeyCode::F2) {
+        context.enabled = !context.enabled;
+    }
+}
+
+fn gizmos_enabled(gizmos: Res<DebugGizmos>) -> ShouldRun {
+    if **gizmos {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn line_transform(start: Vec2, end: Vec2) -> Transform {
+    let diff = end - start;
+    Transform {
+        translation: ((start + end) / 2.0).extend(LINE_Z),
+        rotation: Quat::from_rotation_z(diff.y.atan2(diff.x)),
+        scale: Vec3::new(diff.length(), LINE_WIDTH, 1.0),
+    }
+}
+
+fn draw_debug_gizmos(
+    mut commands: Commands,
+    mut lines: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<GizmoLine>>,
+    bullets: Query<(&Transform, &Bullet), Without<GizmoLine>>,
+    player: Query<(&Transform, &MoveDir), (With<Player>, Without<GizmoLine>)>,
+    enemies: Query<(&Transform, &AiState), (With<Enemy>, Without<GizmoLine>)>,
+    fixed: Res<FixedStep>,
+) {
+    let mut segments = Vec::new();
+
+    for (tf, bullet) in &bullets {
+        let pos = tf.translation.xy();
+        segments.push((pos, pos + VELOCITY_SCALE * bullet.velocity(), Color::YELLOW));
+    }
+
+    for (tf, move_dir) in &player {
+        let pos = tf.translation.xy();
+        let velocity = **move_dir / fixed.delta_seconds();
+        segments.push((pos, pos + MOVE_SCALE * velocity, Color::GREEN));
+    }
+
+    let players: Vec<Vec2> = player.iter().map(|(tf, _)| tf.translation.xy()).collect();
+    for (tf, state) in &enemies {
+        let pos = tf.translation.xy();
+        // Chasing enemies go after the nearest player
+        match (*state, nearest(players.iter().copied(), pos)) {
+            (AiState::Chasing, Some(target)) => segments.push((pos, target, Color::RED)),
+            (AiState::Searching { last_known }, _) => {
+                segments.push((pos, last_known, Color::ORANGE))
+            }
+            _ => {}
+        }
+    }
+
+    let mut segments = segments.into_iter();
+    for (mut tf, mut sprite, mut visibility) in &mut lines {
+        match segments.next() {
+            Some((start, end, color)) => {
+                *tf = line_transform(start, end);
+                sprite.color = color;
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+    // Grow the pool for whatever didn't fit, these are reused from the next frame on
+    for (start, end, color) in segments {
+        commands.spawn((
+            Name::new("Gizmo line"),
+            GizmoLine,
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::ONE),
+                    ..default()
+                },
+                transform: line_transform(start, end),
+                ..default()
+            },
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::diagnostic::{Diagnostics, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+
+use crate::hud::{hud_text_style, HudFont};
+
+/// Shows FPS, frame time and the number of entities in the bottom left corner, toggled with F4.
+pub struct DiagnosticsOverlayPlugin;
+
+impl Plugin for DiagnosticsOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
+            .add_plugin(EntityCountDiagnosticsPlugin)
+            // The HUD font is loaded during `Startup`
+            .add_startup_system_to_stage(StartupStage::PostStartup, spawn_overlay)
+            .add_system(toggle_overlay)
+            .add_system(update_overlay.after(toggle_overlay));
+    }
+}
+
+#[derive(Component)]
+struct DiagnosticsText;
+
+fn spawn_overlay(mut commands: Commands, font: Res<HudFont>) {
+    commands.spawn((
+        Name::new("Diagnostics overlay"),
+        DiagnosticsText,
+        TextBundle {
+            text: Text::from_section("", hud_text_style(&font)),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        },
+    ));
+}
+
+fn toggle_overlay(
+    mut overlay: Query<&mut Visibility, With<DiagnosticsText>>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::F4) {
+        return;
+    }
+    for mut visibility in &mut overlay {
+        visibility.is_visible = !visibility.is_visible;
+    }
+}
+
+fn update_overlay(
+    mut overlay: Query<(&mut Text, &Visibility), With<DiagnosticsText>>,
+    diagnostics: Res<Diagnostics>,
+) {
+    let value = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or(0.0)
+    };
+    for (mut text, visibility) in &mut overlay {
+        if !visibility.is_visible {
+            continue;
+        }
+        text.sections[0].value = format!(
+            "FPS: {:.0}\nFrame time: {:.2} ms\nEntities: {}",
+            value(FrameTimeDiagnosticsPlugin::FPS),
+            1000.0 * value(FrameTimeDiagnosticsPlugin::FRAME_TIME),
+            value(EntityCountDiagnosticsPlugin::ENTITY_COUNT),
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::hot_reload::load_ron_or;
+
+/// Picks the [`Difficulty`] from `--difficulty <easy|normal|hard>`, or normal if it isn't given,
+/// and keeps [`DifficultyScaling`] in sync with it.
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        let difficulty = difficulty_from_args().unwrap_or_default();
+        info!("Playing on {difficulty:?} difficulty");
+        let tiers = DifficultyTiers::load(DIFFICULTY_PATH);
+        app.insert_resource(tiers.get(difficulty))
+            .insert_resource(difficulty)
+            .insert_resource(tiers)
+            .add_system_to_stage(CoreStage::PreUpdate, apply_difficulty);
+    }
+}
+
+/// Where designers can tune the difficulties, see [`DifficultyTiers`] for the format.
+const DIFFICULTY_PATH: &str = "assets/difficulty.ron";
+
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+/// How a difficulty scales the game, as multipliers of the normal values.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyScaling {
+    pub enemy_health: f32,
+    /// How quickly waves follow each other.
+    pub spawn_rate: f32,
+    /// Damage enemies deal by touching the player.
+    pub contact_damage: f32,
+}
+
+impl DifficultyScaling {
+    const NORMAL: Self = Self {
+        enemy_health: 1.0,
+        spawn_rate: 1.0,
+        contact_damage: 1.0,
+    };
+}
+
+/// The scaling of every difficulty, e.g.
+///
+/// ```ron
+/// (
+///     easy: (enemy_health: 0.7, spawn_rate: 0.75, contact_damage: 0.5),
+///     normal: (enemy_health: 1.0, spawn_rate: 1.0, contact_damage: 1.0),
+///     hard: (enemy_health: 1.5, spawn_rate: 1.3, contact_damage: 1.5),
+/// )
+/// ```
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyTiers {
+    pub easy: DifficultyScaling,
+    pub normal: DifficultyScaling,
+    pub hard: DifficultyScaling,
+}
+
+impl Default for DifficultyTiers {
+    fn default() -> Self {
+        Self {
+            easy: DifficultyScaling {
+                enemy_health: 0.7,
+                spawn_rate: 0.75,
+                contact_damage: 0.5,
+            },
+            normal: DifficultyScaling::NORMAL,
+            hard: DifficultyScaling {
+                enemy_health: 1.5,
+                spawn_rate: 1.3,
+                contact_damage: 1.5,
+            },
+        }
+    }
+}
+
+impl DifficultyTiers {
+    pub fn get(&self, difficulty: Difficulty) -> DifficultyScaling {
+        match difficulty {
+            Difficulty::Easy => self.easy,
+            Difficulty::Normal => self.normal,
+            Difficulty::Hard => self.hard,
+        }
+    }
+
+    /// Reads the tiers from `path`, falling back to the built-in ones if it's missing or invalid.
+    fn load(path: &str) -> Self {
+        load_ron_or(path, "default difficulties", Self::default)
+    }
+}
+
+fn difficulty_from_args() -> Option<Difficulty> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--difficulty" {
+            return match args.next()?.to_lowercase().as_str() {
+                "easy" => Some(Difficulty::Easy),
+                "normal" => Some(Difficulty::Normal),
+                "hard" => Some(Difficulty::Hard),
+                other => {
+                    error!("Invalid difficulty: {other}, expected easy, normal or hard");
+                    None
+                }
+            };
+        }
+    }
+    None
+}
+
+/// Switches the scaling over when the difficulty is changed, e.g. from a menu. Enemies that are
+/// already alive keep the health they were spawned with.
+fn apply_difficulty(
+    difficulty: Res<Difficulty>,
+    tiers: Res<DifficultyTiers>,
+    mut scaling: ResMut<DifficultyScaling>,
+) {
+    if difficulty.is_changed() || tiers.is_changed() {
+        *scaling = tiers.get(*difficulty);
+    }
+}
@@ -0,0 +1,663 @@
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use bevy::utils::HashSet;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+
+use crate::arena::ArenaBounds;
+use crate::audio::{ListenerPos, SoundEffects};
+use crate::bullet::{fire_bullets, BulletRange, BulletStats, FireBullet, Shooter};
+use crate::collision::{enemy_groups, WALL_GROUP};
+use crate::difficulty::DifficultyScaling;
+use crate::fixed::{FixedStep, FixedUpdateStage, Interpolated, Simulation};
+use crate::game_time::GameTime;
+use crate::health::{Faction, Health, Invulnerable};
+use crate::pickup::{spawn_pickup, DropTable, EnemyDrops};
+use crate::player::{MoveDir, Player};
+use crate::rng::GameRng;
+use crate::settings::GraphicsQuality;
+use crate::spatial::EnemyGrid;
+use crate::state::{GameState, GameplayEntity};
+use crate::tuning::Tuning;
+use crate::wall::{push_out_of_walls, Wall};
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaveSpawner>()
+            .add_event::<EnemyKilled>()
+            .add_startup_system(setup_enemy_effects)
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_waves))
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .label(Simulation)
+                    .with_system(update_ai_state)
+                    .with_system(move_enemies.after(update_ai_state)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(spawn_waves)
+                    .with_system(contact_damage)
+                    .with_system(ranged_attack.before(fire_bullets))
+                    .with_system(show_stun_stars)
+                    .with_system(wear_off_stuns),
+            )
+            .add_system(enemy_death)
+            .add_system_to_stage(CoreStage::PreUpdate, tune_waves);
+    }
+}
+
+const WANDER_SPEED: f32 = 40.0;
+pub const ENEMY_RADIUS: f32 = 20.0;
+const ENEMY_HEALTH: f32 = 50.0;
+const CONTACT_DAMAGE: f32 = 10.0;
+/// Time after being hit during which further contact deals no damage.
+const CONTACT_INVULNERABILITY: f32 = 1.0;
+/// Distance ranged enemies stop approaching the player at.
+const RANGED_DISTANCE: f32 = 300.0;
+/// Ranged enemies only shoot at the player when they're at most this far away.
+const RANGED_REACH: f32 = 600.0;
+/// Seconds between two shots of a ranged enemy.
+const RANGED_FIRE_INTERVAL: f32 = 1.5;
+/// How much of the player's movement ranged enemies account for, one to aim exactly where the
+/// player will be if they keep moving the same way.
+const RANGED_LEAD: f32 = 0.5;
+/// How quickly knockback fades, it's down to about a third after `1 / KNOCKBACK_DRAG` seconds.
+const KNOCKBACK_DRAG: f32 = 8.0;
+
+#[derive(Component)]
+pub struct Enemy;
+
+/// Velocity an enemy has been knocked back with, on top of where it's heading itself. It fades
+/// out on its own.
+#[derive(Component, Debug, Default, Clone, Copy, Deref, DerefMut)]
+pub struct Knockback(pub Vec2);
+
+/// Keeps its distance from the player and shoots at them rather than closing in.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Ranged {
+    /// Time until the enemy can shoot again.
+    pub cooldown: f32,
+    pub bullet: BulletStats,
+}
+
+impl Default for Ranged {
+    fn default() -> Self {
+        Self {
+            cooldown: RANGED_FIRE_INTERVAL,
+            bullet: BulletStats {
+                speed: 450.0,
+                damage: 10.0,
+                size: 6.0,
+                crit_chance: 0.0,
+                range: BulletRange::Time(2.0),
+                color: Color::ORANGE_RED,
+                ..default()
+            },
+        }
+    }
+}
+
+/// Keeps an enemy from moving or shooting until the timer runs out.
+#[derive(Component, Debug, Clone)]
+pub struct Stunned {
+    pub timer: Timer,
+}
+
+impl Stunned {
+    pub fn from_seconds(duration: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+        }
+    }
+}
+
+/// Particles circling over a stunned enemy.
+#[derive(Component)]
+struct StunStars;
+
+#[derive(Resource)]
+pub struct EnemyEffects {
+    pub stun_stars: Handle<EffectAsset>,
+}
+
+/// Score awarded for killing this enemy.
+#[derive(Component, Clone, Copy)]
+pub struct PointValue(pub u32);
+
+pub struct EnemyKilled {
+    pub points: u32,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum AiState {
+    /// Wandering aimlessly in `heading` until the player comes into view.
+    Idle { heading: Vec2, time_left: f32 },
+    /// The player is in sight and being pursued directly.
+    Chasing,
+    /// Sight of the player was lost, heading to where they were last seen.
+    Searching { last_known: Vec2 },
+}
+
+impl AiState {
+    fn idle(rng: &mut impl Rng) -> Self {
+        let angle = rng.gen_range(0.0..TAU);
+        AiState::Idle {
+            heading: Vec2::new(angle.cos(), angle.sin()),
+            time_left: rng.gen_range(1.0..3.0),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct WaveSpawner {
+    /// Number of enemies in the first wave.
+    pub wave_size: usize,
+    /// How many more enemies each subsequent wave brings.
+    pub wave_growth: usize,
+    /// Time between waves.
+    pub interval: Timer,
+    /// Waves stop spawning enemies once this many are alive.
+    pub max_alive: usize,
+    /// Candidate points tried per enemy before giving up on that slot.
+    pub spawn_attempts: usize,
+    /// Distance beyond the edge of the view at which enemies appear.
+    pub spawn_margin: f32,
+    /// Probability of each enemy in a wave being a ranged one.
+    pub ranged_chance: f32,
+    pub wave: usize,
+}
+
+impl Default for WaveSpawner {
+    fn default() -> Self {
+        let tuning = Tuning::default();
+        Self {
+            wave_size: tuning.wave_size,
+            wave_growth: tuning.wave_growth,
+            interval: Timer::from_seconds(tuning.wave_interval, TimerMode::Repeating),
+            max_alive: tuning.max_alive,
+            spawn_attempts: 5,
+            spawn_margin: 50.0,
+            ranged_chance: 0.2,
+            wave: 0,
+        }
+    }
+}
+
+impl WaveSpawner {
+    fn current_wave_size(&self) -> usize {
+        self.wave_size + self.wave_growth * self.wave
+    }
+}
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    pos: Vec2,
+    drops: DropTable,
+    difficulty: &DifficultyScaling,
+    rng: &mut impl Rng,
+) -> Entity {
+    commands
+        .spawn((
+            Name::new("Enemy"),
+            Enemy,
+            GameplayEntity,
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::RED,
+                    custom_size: Some(Vec2::splat(2.0 * ENEMY_RADIUS)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(0.0)),
+                ..default()
+            },
+            Interpolated::new(pos.extend(0.0)),
+            RigidBody::KinematicPositionBased,
+            Collider::ball(ENEMY_RADIUS),
+            enemy_groups(),
+            Health::new(ENEMY_HEALTH * difficulty.enemy_health),
+            Faction::Enemy,
+            PointValue(10),
+            drops,
+            ActiveEvents::COLLISION_EVENTS,
+            ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_KINEMATIC,
+            AiState::idle(rng),
+        ))
+        .id()
+}
+
+/// Spawns an enemy that keeps its distance and shoots at the player, see [`Ranged`].
+pub fn spawn_ranged_enemy(
+    commands: &mut Commands,
+    pos: Vec2,
+    drops: DropTable,
+    difficulty: &DifficultyScaling,
+    rng: &mut impl Rng,
+) -> Entity {
+    let entity = spawn_enemy(commands, pos, drops, difficulty, rng);
+    commands.entity(entity).insert((
+        Name::new("Ranged enemy"),
+        Sprite {
+            color: Color::MAROON,
+            custom_size: Some(Vec2::splat(2.0 * ENEMY_RADIUS)),
+            ..default()
+        },
+        PointValue(15),
+        Ranged::default(),
+    ));
+    entity
+}
+
+/// Keeps the spawner's rates in sync with the [`Tuning`], without resetting the wave.
+fn tune_waves(tuning: Res<Tuning>, mut spawner: ResMut<WaveSpawner>) {
+    if !tuning.is_changed() {
+        return;
+    }
+    spawner.wave_size = tuning.wave_size;
+    spawner.wave_growth = tuning.wave_growth;
+    spawner.max_alive = tuning.max_alive;
+    spawner
+        .interval
+        .set_duration(Duration::from_secs_f32(tuning.wave_interval));
+}
+
+fn reset_waves(mut spawner: ResMut<WaveSpawner>) {
+    spawner.wave = 0;
+    spawner.interval.reset();
+}
+
+fn spawn_waves(
+    mut commands: Commands,
+    mut spawner: ResMut<WaveSpawner>,
+    enemies: Query<(), With<Enemy>>,
+    camera: Query<&GlobalTransform, With<Camera>>,
+    windows: Res<Windows>,
+    rapier: Res<RapierContext>,
+    drops: Res<EnemyDrops>,
+    bounds: Res<ArenaBounds>,
+    difficulty: Res<DifficultyScaling>,
+    mut rng: ResMut<GameRng>,
+    time: Res<GameTime>,
+) {
+    // Ticking the interval faster makes waves come sooner
+    let delta = time.delta().mul_f32(difficulty.spawn_rate.max(0.0));
+    if !spawner.interval.tick(delta).just_finished() {
+        return;
+    }
+
+    let window = windows.get_primary().unwrap();
+    // With split screen every view is the same size, spawning around one of them is enough
+    let Some(center) = camera.iter().next().map(|tf| tf.translation().xy()) else { return };
+    // Anything further than half the view's diagonal is guaranteed to be off screen
+    let spawn_distance =
+        Vec2::new(window.width(), window.height()).length() / 2.0 + spawner.spawn_margin;
+
+    let alive = enemies.iter().count();
+    let to_spawn = spawner
+        .current_wave_size()
+        .min(spawner.max_alive.saturating_sub(alive));
+
+    let shape = Collider::ball(ENEMY_RADIUS);
+    for _ in 0..to_spawn {
+        for _ in 0..spawner.spawn_attempts {
+            let angle = rng.gen_range(0.0..TAU);
+            let pos = center + spawn_distance * Vec2::new(angle.cos(), angle.sin());
+            let inside = bounds.clamp(pos, Vec2::splat(ENEMY_RADIUS)) == pos;
+            if inside
+                && rapier
+                    .intersection_with_shape(pos, 0.0, &shape, wall_filter())
+                    .is_none()
+            {
+                let drops = drops.0.clone();
+                if rng.gen::<f32>() < spawner.ranged_chance {
+                    spawn_ranged_enemy(&mut commands, pos, drops, &difficulty, &mut **rng);
+                } else {
+                    spawn_enemy(&mut commands, pos, drops, &difficulty, &mut **rng);
+                }
+                break;
+            }
+        }
+    }
+
+    spawner.wave += 1;
+}
+
+pub fn wall_filter() -> QueryFilter<'static> {
+    QueryFilter::new().groups(CollisionGroups::new(Group::ALL, WALL_GROUP))
+}
+
+/// The closest of `targets` to `pos`, e.g. the nearest player.
+pub fn nearest(targets: impl IntoIterator<Item = Vec2>, pos: Vec2) -> Option<Vec2> {
+    targets
+        .into_iter()
+        .min_by(|a, b| a.distance_squared(pos).total_cmp(&b.distance_squared(pos)))
+}
+
+fn has_line_of_sight(rapier: &RapierContext, from: Vec2, to: Vec2) -> bool {
+    // Only walls block sight, other enemies and the player are ignored
+    rapier
+        .cast_ray(from, to - from, 1.0, true, wall_filter())
+        .is_none()
+}
+
+fn update_ai_state(
+    mut enemies: Query<(&Transform, &mut AiState), (With<Enemy>, Without<Stunned>)>,
+    player: Query<&Transform, With<Player>>,
+    rapier: Res<RapierContext>,
+    fixed: Res<FixedStep>,
+    mut rng: ResMut<GameRng>,
+) {
+    let players: Vec<Vec2> = player.iter().map(|tf| tf.translation.xy()).collect();
+
+    for (tf, mut state) in &mut enemies {
+        let pos = tf.translation.xy();
+        let in_sight = players
+            .iter()
+            .copied()
+            .filter(|&player_pos| has_line_of_sight(&rapier, pos, player_pos));
+        let visible_player = nearest(in_sight, pos);
+
+        let next = match (*state, visible_player) {
+            (_, Some(_)) => AiState::Chasing,
+            (AiState::Chasing, None) => match nearest(players.iter().copied(), pos) {
+                Some(last_known) => AiState::Searching { last_known },
+                None => AiState::idle(&mut **rng),
+            },
+            (AiState::Searching { last_known }, None) => {
+                if pos.distance(last_known) < ENEMY_RADIUS {
+                    AiState::idle(&mut **rng)
+                } else {
+                    *state
+                }
+            }
+            (AiState::Idle { heading, time_left }, None) => {
+                let time_left = time_left - fixed.delta_seconds();
+                if time_left <= 0.0 {
+                    AiState::idle(&mut **rng)
+                } else {
+                    AiState::Idle { heading, time_left }
+                }
+            }
+        };
+
+        if *state != next {
+            *state = next;
+        }
+    }
+}
+
+/// Sums up how strongly the enemy at `pos` is pushed away from other enemies within the
+/// separation radius, the closer they are the stronger.
+fn separation(entity: Entity, pos: Vec2, neighbors: &EnemyGrid, radius: f32) -> Vec2 {
+    let mut push = Vec2::ZERO;
+    for (neighbor, neighbor_pos) in neighbors.query_nearby(pos, radius) {
+        let away = pos - neighbor_pos;
+        let distance = away.length();
+        // Enemies exactly on top of each other have no direction to push each other in
+        if neighbor != entity && distance > f32::EPSILON {
+            push += (1.0 - distance / radius).max(0.0) * away / distance;
+        }
+    }
+    push
+}
+
+fn move_enemies(
+    mut enemies: Query<
+        (
+            Entity,
+            &mut Transform,
+            &Collider,
+            &AiState,
+            Option<&Ranged>,
+            Option<&mut Knockback>,
+        ),
+        (With<Enemy>, Without<Player>, Without<Stunned>),
+    >,
+    player: Query<&Transform, With<Player>>,
+    walls: Query<(&Collider, &GlobalTransform), With<Wall>>,
+    // Where every enemy was at the start of this step
+    neighbors: Res<EnemyGrid>,
+    rapier: Res<RapierContext>,
+    bounds: Res<ArenaBounds>,
+    fixed: Res<FixedStep>,
+    tuning: Res<Tuning>,
+) {
+    let players: Vec<Vec2> = player.iter().map(|tf| tf.translation.xy()).collect();
+
+    for (entity, mut tf, collider, state, ranged, knockback) in &mut enemies {
+        let pos = tf.translation.xy();
+        let (speed, heading) = match *state {
+            AiState::Idle { heading, .. } => (WANDER_SPEED, heading),
+            AiState::Chasing => match nearest(players.iter().copied(), pos) {
+                // Ranged enemies hold their position once they're close enough to shoot
+                Some(target) if ranged.is_some() && pos.distance(target) <= RANGED_DISTANCE => {
+                    (tuning.enemy_speed, Vec2::ZERO)
+                }
+                Some(target) => (tuning.enemy_speed, (target - pos).normalize_or_zero()),
+                None => (tuning.enemy_speed, Vec2::ZERO),
+            },
+            AiState::Searching { last_known } => {
+                (tuning.enemy_speed, (last_known - pos).normalize_or_zero())
+            }
+        };
+        let apart = separation(entity, pos, &neighbors, tuning.separation_radius);
+        let steer = tuning.chase_weight * heading + tuning.separation_weight * apart;
+        let velocity = speed * steer.clamp_length_max(1.0);
+        let velocity = match knockback {
+            Some(mut knockback) => {
+                let pushed = velocity + **knockback;
+                **knockback *= (-KNOCKBACK_DRAG * fixed.delta_seconds()).exp();
+                pushed
+            }
+            None => velocity,
+        };
+        // Enemies are moved by hand, so nothing but this keeps them out of walls
+        let radius = collider
+            .as_ball()
+            .map_or(ENEMY_RADIUS, |ball| ball.radius());
+        let pos = pos + velocity * fixed.delta_seconds();
+        let pos = push_out_of_walls(pos, radius, &rapier, wall_filter(), &walls);
+        let pos = bounds.clamp(pos, Vec2::splat(radius));
+        tf.translation = pos.extend(tf.translation.z);
+    }
+}
+
+/// Shoots at the nearest player in sight and close enough, aiming a little ahead of where they're
+/// moving.
+fn ranged_attack(
+    mut enemies: Query<(Entity, &Transform, &mut Ranged), (With<Enemy>, Without<Stunned>)>,
+    player: Query<(&Transform, &MoveDir), With<Player>>,
+    rapier: Res<RapierContext>,
+    audio: Res<Audio>,
+    sounds: Res<SoundEffects>,
+    listener: Res<ListenerPos>,
+    mut fire: EventWriter<FireBullet>,
+    fixed: Res<FixedStep>,
+    time: Res<GameTime>,
+) {
+    for (entity, tf, mut ranged) in &mut enemies {
+        ranged.cooldown = (ranged.cooldown - time.delta_seconds()).max(0.0);
+        if ranged.cooldown > 0.0 {
+            continue;
+        }
+        let pos = tf.translation.xy();
+        let in_reach = player
+            .iter()
+            .map(|(player_tf, move_dir)| (player_tf.translation.xy(), move_dir))
+            .filter(|&(target, _)| {
+                pos.distance(target) <= RANGED_REACH && has_line_of_sight(&rapier, pos, target)
+            })
+            .min_by(|(a, _), (b, _)| a.distance_squared(pos).total_cmp(&b.distance_squared(pos)));
+        let Some((target, move_dir)) = in_reach else { continue };
+        let distance = pos.distance(target);
+
+        // The player moves by `MoveDir` every step, lead them by where that takes them by the
+        // time the bullet gets there
+        let velocity = **move_dir / fixed.delta_seconds();
+        let flight_time = distance / ranged.bullet.speed;
+        let aim = target + RANGED_LEAD * flight_time * velocity;
+        let Some(dir) = (aim - pos).try_normalize() else { continue };
+
+        ranged.cooldown = RANGED_FIRE_INTERVAL;
+        fire.send(FireBullet {
+            origin: tf.translation,
+            dir,
+            stats: ranged.bullet,
+            shooter: Shooter {
+                entity,
+                faction: Faction::Enemy,
+                velocity: Vec2::ZERO,
+                pos,
+                radius: ENEMY_RADIUS,
+            },
+        });
+        sounds.play_at(&audio, &sounds.gunshot, 0.4, pos, &listener);
+    }
+}
+
+fn setup_enemy_effects(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    quality: Res<GraphicsQuality>,
+) {
+    let mut gradient = Gradient::new();
+    gradient.add_key(0.0, Vec4::new(1.0, 1.0, 0.3, 1.0));
+    gradient.add_key(1.0, Vec4::new(1.0, 1.0, 0.3, 0.0));
+
+    let stun_stars = effects.add(
+        EffectAsset {
+            name: "Stun stars".into(),
+            capacity: quality.capacity(256),
+            spawner: Spawner::rate(quality.particles(20.0).into()),
+            ..default()
+        }
+        .init(InitPositionCircleModifier {
+            radius: 12.0,
+            dimension: ShapeDimension::Surface,
+            ..default()
+        })
+        .init(InitVelocityCircleModifier {
+            speed: Value::Uniform((10.0, 30.0)),
+            ..default()
+        })
+        .init(InitLifetimeModifier {
+            lifetime: Value::Single(0.5),
+        })
+        .render(SizeOverLifetimeModifier {
+            gradient: Gradient::constant(Vec2::splat(4.0)),
+        })
+        .render(ColorOverLifetimeModifier { gradient }),
+    );
+    commands.insert_resource(EnemyEffects { stun_stars });
+}
+
+/// Puts stars over the heads of newly stunned enemies.
+fn show_stun_stars(
+    mut commands: Commands,
+    stunned: Query<Entity, Added<Stunned>>,
+    effects: Option<Res<EnemyEffects>>,
+) {
+    let Some(effects) = effects else { return };
+    for entity in &stunned {
+        commands.entity(entity).with_children(|parent| {
+            parent.spawn((
+                Name::new("Stun stars"),
+                StunStars,
+                SpatialBundle::from_transform(Transform::from_xyz(0.0, ENEMY_RADIUS, 0.1)),
+                ParticleEffect::new(effects.stun_stars.clone()).with_z_layer_2d(Some(0.4)),
+            ));
+        });
+    }
+}
+
+/// Lets enemies move and shoot again once their stun wears off, taking the stars away.
+fn wear_off_stuns(
+    mut commands: Commands,
+    mut stunned: Query<(Entity, &mut Stunned, Option<&Children>)>,
+    stars: Query<(), With<StunStars>>,
+    time: Res<GameTime>,
+) {
+    for (entity, mut stun, children) in &mut stunned {
+        if !stun.timer.tick(time.delta()).finished() {
+            continue;
+        }
+        commands.entity(entity).remove::<Stunned>();
+        let Some(children) = children else { continue };
+        for &child in children.iter() {
+            if stars.contains(child) {
+                commands.entity(child).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn contact_damage(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    // Which enemies each player is touching, as (player, enemy) pairs
+    mut touching: Local<HashSet<(Entity, Entity)>>,
+    enemies: Query<(), With<Enemy>>,
+    mut player: Query<(Entity, &mut Health, Option<&Invulnerable>), With<Player>>,
+    difficulty: Res<DifficultyScaling>,
+) {
+    for collision in collisions.iter() {
+        let (e1, e2, started) = match *collision {
+            CollisionEvent::Started(e1, e2, _) => (e1, e2, true),
+            CollisionEvent::Stopped(e1, e2, _) => (e1, e2, false),
+        };
+        let pair = match (player.contains(e1), player.contains(e2)) {
+            (true, _) => (e1, e2),
+            (_, true) => (e2, e1),
+            _ => continue,
+        };
+        if started {
+            touching.insert(pair);
+        } else {
+            touching.remove(&pair);
+        }
+    }
+    touching.retain(|&(touched, enemy)| player.contains(touched) && enemies.contains(enemy));
+
+    for (entity, mut health, invulnerable) in &mut player {
+        if invulnerable.is_none() && touching.iter().any(|&(touched, _)| touched == entity) {
+            health.damage(CONTACT_DAMAGE * difficulty.contact_damage);
+            commands
+                .entity(entity)
+                .insert(Invulnerable::from_seconds(CONTACT_INVULNERABILITY));
+        }
+    }
+}
+
+fn enemy_death(
+    mut commands: Commands,
+    enemies: Query<(Entity, &Transform, &Health, &PointValue, Option<&DropTable>), With<Enemy>>,
+    mut killed: EventWriter<EnemyKilled>,
+    audio: Res<Audio>,
+    sounds: Res<SoundEffects>,
+    listener: Res<ListenerPos>,
+    mut rng: ResMut<GameRng>,
+) {
+    for (entity, tf, health, points, drops) in &enemies {
+        if health.is_dead() {
+            commands.entity(entity).despawn_recursive();
+            if let Some(kind) = drops.and_then(|drops| drops.roll(&mut **rng)) {
+                spawn_pickup(&mut commands, tf.translation.xy(), kind);
+            }
+            sounds.play_at(
+                &audio,
+                &sounds.enemy_death,
+                0.8,
+                tf.translation.xy(),
+                &listener,
+            );
+            killed.send(EnemyKilled { points: points.0 });
+        }
+    }
+}
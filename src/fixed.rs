@@ -0,0 +1,144 @@
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+use crate::game_time::GameTime;
+use crate::state::GameState;
+
+pub struct FixedTimestepPlugin;
+
+impl Plugin for FixedTimestepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FixedStep::new(1.0 / 60.0))
+            .add_stage_after(
+                CoreStage::Update,
+                FixedUpdateStage,
+                SystemStage::parallel().with_run_criteria(fixed_step),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, restore_simulated_positions)
+            .add_system_to_stage(
+                FixedUpdateStage,
+                record_simulated_positions.after(Simulation),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_positions.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Frames longer than this are treated as if they were this long, so a hitch doesn't make the
+/// simulation run a huge number of steps to catch up.
+const MAX_FRAME_TIME: f32 = 0.25;
+
+/// Runs once per fixed timestep while the game is being played, possibly several times per
+/// frame. The movement of the player, bullets and enemies is simulated here so that it's
+/// independent of the framerate and recorded inputs replay identically:
+/// `move_player`, `update_player_pos`, `Bullet::move_bullet`, `update_ai_state` and
+/// `move_enemies`. Systems reading input or only affecting visuals stay in `CoreStage::Update`.
+#[derive(StageLabel)]
+pub struct FixedUpdateStage;
+
+/// Label for every system in [`FixedUpdateStage`] that moves entities.
+#[derive(SystemLabel)]
+pub struct Simulation;
+
+#[derive(Resource)]
+pub struct FixedStep {
+    step: f32,
+    accumulator: f32,
+    looping: bool,
+    frame_delta: Option<f32>,
+}
+
+impl FixedStep {
+    pub fn new(step: f32) -> Self {
+        Self {
+            step,
+            accumulator: 0.0,
+            looping: false,
+            frame_delta: None,
+        }
+    }
+
+    /// Makes the current frame advance the simulation by `delta` instead of the real frame time,
+    /// used to run the same number of steps as a recorded frame when replaying it.
+    pub fn override_frame_delta(&mut self, delta: f32) {
+        self.frame_delta = Some(delta);
+    }
+
+    /// The length of a single simulation step, use this instead of `Time` in fixed systems.
+    pub fn delta_seconds(&self) -> f32 {
+        self.step
+    }
+
+    /// How far between the previous and the current step the rendered frame is.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+}
+
+fn fixed_step(
+    mut fixed: ResMut<FixedStep>,
+    time: Res<GameTime>,
+    state: Res<State<GameState>>,
+) -> ShouldRun {
+    if *state.current() != GameState::Playing {
+        fixed.looping = false;
+        return ShouldRun::No;
+    }
+
+    if !fixed.looping {
+        let delta = fixed.frame_delta.take().unwrap_or(time.delta_seconds());
+        fixed.accumulator += delta.min(MAX_FRAME_TIME);
+    }
+
+    if fixed.accumulator >= fixed.step {
+        fixed.accumulator -= fixed.step;
+        fixed.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        fixed.looping = false;
+        ShouldRun::No
+    }
+}
+
+/// Entities moved in [`FixedUpdateStage`] that should be rendered smoothly between steps.
+#[derive(Component)]
+pub struct Interpolated {
+    previous: Vec3,
+    /// Where the last step left the entity, `Transform` lags behind this while rendering.
+    pub current: Vec3,
+}
+
+impl Interpolated {
+    pub fn new(pos: Vec3) -> Self {
+        Self {
+            previous: pos,
+            current: pos,
+        }
+    }
+}
+
+fn restore_simulated_positions(mut entities: Query<(&mut Transform, &Interpolated)>) {
+    for (mut tf, interpolated) in &mut entities {
+        tf.translation = interpolated.current;
+    }
+}
+
+fn record_simulated_positions(mut entities: Query<(&Transform, &mut Interpolated)>) {
+    for (tf, mut interpolated) in &mut entities {
+        interpolated.previous = interpolated.current;
+        interpolated.current = tf.translation;
+    }
+}
+
+pub fn interpolate_positions(
+    mut entities: Query<(&mut Transform, &Interpolated)>,
+    fixed: Res<FixedStep>,
+) {
+    let alpha = fixed.alpha();
+    for (mut tf, interpolated) in &mut entities {
+        tf.translation = interpolated.previous.lerp(interpolated.current, alpha);
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeSystem;
+use bevy_rapier2d::prelude::*;
+
+use crate::enemy::EnemyKilled;
+use crate::health::DamageDealt;
+
+pub struct GameTimePlugin;
+
+impl Plugin for GameTimePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeScale>()
+            .init_resource::<GameTime>()
+            .init_resource::<HitStop>()
+            .add_system_to_stage(CoreStage::First, update_game_time.after(TimeSystem))
+            .add_system(toggle_slow_motion)
+            .add_system(scale_physics)
+            .add_system(trigger_hit_stop)
+            .add_system(end_hit_stop.after(trigger_hit_stop).before(scale_physics));
+    }
+}
+
+/// Time scale while slow motion is toggled on.
+const SLOW_MOTION: f32 = 0.25;
+/// Real time the game freezes for on a critical hit or kill.
+const HIT_STOP_DURATION: f32 = 0.05;
+/// Hit stops triggered in quick succession extend each other, but never past this.
+const MAX_HIT_STOP: f32 = 0.15;
+
+/// How fast gameplay runs compared to real time, e.g. 0.5 for half speed.
+#[derive(Resource, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct TimeScale(pub f32);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// [`Time`] scaled by [`TimeScale`]. Gameplay systems use this instead of `Time` so that they
+/// all slow down together, only things like UI and camera shake should use the real time.
+#[derive(Resource, Default, Debug)]
+pub struct GameTime {
+    delta: Duration,
+    elapsed: Duration,
+}
+
+impl GameTime {
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    pub fn advance(&mut self, delta: Duration) {
+        self.delta = delta;
+        self.elapsed += delta;
+    }
+}
+
+/// Briefly freezes the game to give big hits more punch. Input keeps being read while frozen,
+/// only [`GameTime`] stands still.
+#[derive(Resource, Default)]
+pub struct HitStop {
+    /// Runs in real time, `None` when there is no hit stop going on.
+    timer: Option<Timer>,
+    /// The time scale from before the hit stop, restored once it's over.
+    restore: f32,
+}
+
+impl HitStop {
+    pub fn trigger(&mut self, duration: f32, scale: &mut TimeScale) {
+        match &mut self.timer {
+            Some(timer) => {
+                let total = (timer.duration().as_secs_f32() + duration).min(MAX_HIT_STOP);
+                timer.set_duration(Duration::from_secs_f32(total));
+            }
+            None => {
+                self.timer = Some(Timer::from_seconds(
+                    duration.min(MAX_HIT_STOP),
+                    TimerMode::Once,
+                ));
+                self.restore = **scale;
+                **scale = 0.0;
+            }
+        }
+    }
+}
+
+fn update_game_time(time: Res<Time>, scale: Res<TimeScale>, mut game_time: ResMut<GameTime>) {
+    game_time.advance(time.delta().mul_f32(scale.max(0.0)));
+}
+
+fn toggle_slow_motion(mut scale: ResMut<TimeScale>, keys: Res<Input<KeyCode>>) {
+    if keys.just_pressed(KeyCode::T) {
+        **scale = if **scale == 1.0 { SLOW_MOTION } else { 1.0 };
+    }
+}
+
+fn trigger_hit_stop(
+    mut hit_stop: ResMut<HitStop>,
+    mut scale: ResMut<TimeScale>,
+    mut damaged: EventReader<DamageDealt>,
+    mut killed: EventReader<EnemyKilled>,
+) {
+    let crits = damaged.iter().filter(|damage| damage.crit).count();
+    let kills = killed.iter().count();
+    if crits + kills > 0 {
+        hit_stop.trigger(HIT_STOP_DURATION, &mut scale);
+    }
+}
+
+fn end_hit_stop(mut hit_stop: ResMut<HitStop>, mut scale: ResMut<TimeScale>, time: Res<Time>) {
+    let Some(timer) = &mut hit_stop.timer else { return };
+    if timer.tick(time.delta()).finished() {
+        hit_stop.timer = None;
+        **scale = hit_stop.restore;
+    }
+}
+
+fn scale_physics(scale: Res<TimeScale>, mut rapier_config: ResMut<RapierConfiguration>) {
+    if !scale.is_changed() {
+        return;
+    }
+    match &mut rapier_config.timestep_mode {
+        TimestepMode::Variable { time_scale, .. }
+        | TimestepMode::Interpolated { time_scale, .. } => *time_scale = **scale,
+        TimestepMode::Fixed { .. } => {}
+    }
+}
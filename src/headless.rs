@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::arena::ArenaBounds;
+use crate::audio::{ListenerPos, SoundEffects};
+use crate::bullet::{BulletEffects, FireBullet, FriendlyFire, SweptCollision};
+use crate::camera::ScreenShake;
+use crate::fixed::{FixedStep, FixedTimestepPlugin};
+use crate::game_time::GameTime;
+use crate::health::DamageDealt;
+use crate::rng::GameRng;
+use crate::spatial::EnemyGrid;
+use crate::state::GameState;
+use crate::tuning::Tuning;
+
+/// Builds an app that can run gameplay systems without a window, GPU or audio device, so tests
+/// only have to add the systems they're checking and spawn some entities.
+///
+/// Time doesn't pass on its own, [`run_fixed_steps`] advances it. Effects and sounds get
+/// default handles: without `HanabiPlugin` a `ParticleEffect` is just an inert component, and
+/// sounds are queued on an `Audio` nothing plays, so bullets can be spawned as usual.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(HierarchyPlugin)
+        .add_state(GameState::Playing)
+        .add_plugin(FixedTimestepPlugin)
+        .init_resource::<GameTime>()
+        .init_resource::<Tuning>()
+        .init_resource::<ArenaBounds>()
+        .init_resource::<FriendlyFire>()
+        .init_resource::<SweptCollision>()
+        .init_resource::<ScreenShake>()
+        .init_resource::<ListenerPos>()
+        .init_resource::<EnemyGrid>()
+        .init_resource::<Audio>()
+        .insert_resource(RapierContext::default())
+        .insert_resource(GameRng::seeded(0))
+        .insert_resource(BulletEffects {
+            trail: default(),
+            debris: default(),
+            metal_debris: default(),
+            wood_debris: default(),
+            stone_debris: default(),
+            exit_debris: default(),
+            explosion: default(),
+        })
+        .insert_resource(SoundEffects {
+            gunshot: default(),
+            impact: default(),
+            footstep: default(),
+            enemy_death: default(),
+        })
+        .add_event::<DamageDealt>()
+        .add_event::<FireBullet>();
+    app
+}
+
+/// Runs `steps` frames that each advance [`GameTime`] by exactly one fixed step, so the fixed
+/// update stage runs once per frame. Interpolated entities are then rendered at the previous
+/// step, so check `Interpolated::current` for where the simulation put them.
+pub fn run_fixed_steps(app: &mut App, steps: u32) {
+    let step = app.world.resource::<FixedStep>().delta_seconds();
+    for _ in 0..steps {
+        app.world
+            .resource_mut::<GameTime>()
+            .advance(Duration::from_secs_f32(step));
+        // Converting to a `Duration` and back could round it to just under a step
+        app.world
+            .resource_mut::<FixedStep>()
+            .override_frame_delta(step);
+        app.update();
+    }
+}
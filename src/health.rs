@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game_time::GameTime;
+use crate::state::GameState;
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Health>()
+            .add_event::<DamageDealt>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(tick_invulnerability),
+            );
+    }
+}
+
+/// How many times per second an invulnerable entity blinks.
+const FLASH_RATE: f32 = 10.0;
+
+#[derive(Reflect, Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.0);
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+/// Sent whenever a bullet or explosion damages something.
+pub struct DamageDealt {
+    pub target: Entity,
+    /// Where the damage was dealt, e.g. the bullet's point of impact.
+    pub point: Vec2,
+    pub amount: f32,
+    /// Whether this was a critical hit, `amount` already includes the multiplier.
+    pub crit: bool,
+}
+
+/// Which side an entity fights for, bullets don't hit their own faction unless friendly fire
+/// is enabled.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
+}
+
+/// Entities with this component can't be damaged until the timer runs out.
+#[derive(Component)]
+pub struct Invulnerable(pub Timer);
+
+impl Invulnerable {
+    pub fn from_seconds(duration: f32) -> Self {
+        Self(Timer::from_seconds(duration, TimerMode::Once))
+    }
+}
+
+fn tick_invulnerability(
+    mut commands: Commands,
+    mut invulnerable: Query<(Entity, &mut Invulnerable, &mut Visibility)>,
+    time: Res<GameTime>,
+) {
+    for (entity, mut invulnerable, mut visibility) in &mut invulnerable {
+        if invulnerable.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+            visibility.is_visible = true;
+        } else {
+            let blink = (invulnerable.0.elapsed_secs() * FLASH_RATE * 2.0) as u32;
+            visibility.is_visible = blink % 2 == 0;
+        }
+    }
+}
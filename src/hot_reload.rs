@@ -0,0 +1,90 @@
+use std::fs;
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::de::DeserializeOwned;
+
+/// Reads and parses the RON file at `path`.
+pub fn read_ron<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let ron = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    ron::from_str(&ron).map_err(|err| err.to_string())
+}
+
+/// Reads the RON file at `path`, falling back to `built_in` if it's missing or invalid. `what`
+/// names the fallback in the log, e.g. "default level".
+pub fn load_ron_or<T: DeserializeOwned>(path: &str, what: &str, built_in: impl FnOnce() -> T) -> T {
+    read_ron(path).unwrap_or_else(|err| {
+        info!("Using the {what}, couldn't load {path}: {err}");
+        built_in()
+    })
+}
+
+/// Polls files' modification times every so often, so they can be reloaded when they change.
+pub struct FileWatcher {
+    /// When the watcher was made, which is right after the files were loaded at startup.
+    started: SystemTime,
+    since_check: f32,
+    modified: HashMap<String, SystemTime>,
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self {
+            started: SystemTime::now(),
+            since_check: 0.0,
+            modified: HashMap::default(),
+        }
+    }
+}
+
+impl FileWatcher {
+    /// Whether it's time to check the files again, `interval` seconds after the last check.
+    pub fn due(&mut self, delta: f32, interval: f32) -> bool {
+        self.since_check += delta;
+        if self.since_check < interval {
+            return false;
+        }
+        self.since_check = 0.0;
+        true
+    }
+
+    /// Whether `path` changed since it was last checked. The first time a file is seen it counts
+    /// as changed if it was modified after the watcher was made, so a file that was missing or
+    /// invalid at startup is still picked up once it's fixed.
+    pub fn changed(&mut self, path: &str) -> bool {
+        let Ok(modified) = fs::metadata(path).and_then(|meta| meta.modified()) else {
+            return false;
+        };
+        match self.modified.insert(path.to_owned(), modified) {
+            Some(last) => last != modified,
+            None => modified > self.started,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn file_written_after_startup_counts_as_changed() {
+        let path = std::env::temp_dir().join("basic_game_file_watcher_test.ron");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let mut watcher = FileWatcher::default();
+        assert!(!watcher.changed(path));
+
+        // File times can be a few milliseconds coarser than the clock
+        thread::sleep(Duration::from_millis(50));
+        fs::write(path, "()").unwrap();
+        assert!(watcher.changed(path));
+        assert!(!watcher.changed(path));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::boss::Boss;
+use crate::health::Health;
+use crate::player::{PlayerCount, PlayerNumber};
+use crate::score::{Combo, Score};
+use crate::tuning::Tuning;
+use crate::weapon::{Heat, Loadout, Magazine};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_hud)
+            .add_system(update_hud)
+            .add_system(update_boss_bar)
+            .add_system(update_score_text)
+            .add_system(update_combo_text);
+    }
+}
+
+const HEALTH_BAR_SIZE: Vec2 = Vec2::new(200.0, 20.0);
+const BOSS_BAR_SIZE: Vec2 = Vec2::new(600.0, 24.0);
+
+/// The health bar of the player with this number.
+#[derive(Component)]
+struct HealthBar(usize);
+
+/// Shown at the top of the screen while a boss is alive.
+#[derive(Component)]
+struct BossBar;
+
+#[derive(Component)]
+struct BossBarFill;
+
+/// The ammo or heat of the player with this number.
+#[derive(Component)]
+struct AmmoText(usize);
+
+#[derive(Component)]
+struct ScoreText;
+
+#[derive(Component)]
+struct ComboText;
+
+#[derive(Resource)]
+pub struct HudFont(pub Handle<Font>);
+
+pub fn hud_text_style(font: &HudFont) -> TextStyle {
+    TextStyle {
+        font: font.0.clone(),
+        font_size: 24.0,
+        color: Color::WHITE,
+    }
+}
+
+/// Spawns the health bar and ammo text of player `number`.
+fn spawn_player_stats(parent: &mut ChildBuilder, number: usize, font: &HudFont) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(HEALTH_BAR_SIZE.x), Val::Px(HEALTH_BAR_SIZE.y)),
+                ..default()
+            },
+            background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..default()
+                    },
+                    background_color: Color::RED.into(),
+                    ..default()
+                },
+                HealthBar(number),
+            ));
+        });
+    parent.spawn((
+        TextBundle::from_section("0 / 0", hud_text_style(font)),
+        AmmoText(number),
+    ));
+}
+
+/// Spawns player one's stats along with the score in the top left corner, and every other
+/// player's stats in the top right.
+fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>, count: Res<PlayerCount>) {
+    let font = HudFont(asset_server.load("fonts/FiraMono-Medium.ttf"));
+
+    commands
+        .spawn((
+            Name::new("HUD"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.0),
+                        top: Val::Px(10.0),
+                        ..default()
+                    },
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            spawn_player_stats(parent, 0, &font);
+            parent.spawn((
+                TextBundle::from_section("Score: 0", hud_text_style(&font)),
+                ScoreText,
+            ));
+            parent.spawn((
+                TextBundle::from_section("", hud_text_style(&font)),
+                ComboText,
+            ));
+        });
+
+    if **count > 1 {
+        commands
+            .spawn((
+                Name::new("Other players' HUD"),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            right: Val::Px(10.0),
+                            top: Val::Px(10.0),
+                            ..default()
+                        },
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::FlexEnd,
+                        ..default()
+                    },
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                for number in 1..**count {
+                    spawn_player_stats(parent, number, &font);
+                }
+            });
+    }
+
+    commands
+        .spawn((
+            Name::new("Boss health bar"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(10.0),
+                        ..default()
+                    },
+                    size: Size::new(Val::Percent(100.0), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                visibility: Visibility { is_visible: false },
+                ..default()
+            },
+            BossBar,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("BOSS", hud_text_style(&font)));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(BOSS_BAR_SIZE.x), Val::Px(BOSS_BAR_SIZE.y)),
+                        ..default()
+                    },
+                    background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                                ..default()
+                            },
+                            background_color: Color::INDIGO.into(),
+                            ..default()
+                        },
+                        BossBarFill,
+                    ));
+                });
+        });
+
+    commands.insert_resource(font);
+}
+
+/// The values currently shown in the HUD, used to skip needless UI updates.
+#[derive(Default, PartialEq, Clone, Copy)]
+struct HudValues {
+    health_fraction: f32,
+    rounds: u32,
+    capacity: u32,
+    /// Heat in percent when holding a weapon that overheats instead of using up rounds.
+    heat: Option<u32>,
+    overheated: bool,
+}
+
+fn update_hud(
+    player: Query<(&PlayerNumber, &Health, &Magazine, &Loadout, &Heat)>,
+    mut health_bars: Query<(&HealthBar, &mut Style)>,
+    mut ammo_texts: Query<(&AmmoText, &mut Text)>,
+    count: Res<PlayerCount>,
+    mut shown: Local<HashMap<usize, HudValues>>,
+) {
+    for number in 0..**count {
+        // A dead player has been despawned, so show an empty HUD rather than stale values
+        let values = player
+            .iter()
+            .find(|(player_number, ..)| ***player_number == number)
+            .map_or_else(
+                HudValues::default,
+                |(_, health, magazine, loadout, heat)| HudValues {
+                    health_fraction: health.current / health.max,
+                    rounds: magazine.rounds,
+                    capacity: magazine.capacity,
+                    heat: loadout
+                        .current()
+                        .heat_per_shot
+                        .map(|_| (100.0 * heat.current / heat.max).round() as u32),
+                    overheated: heat.overheated,
+                },
+            );
+
+        if shown.insert(number, values) == Some(values) {
+            continue;
+        }
+
+        for (bar, mut style) in &mut health_bars {
+            if bar.0 == number {
+                style.size.width = Val::Percent(100.0 * values.health_fraction);
+            }
+        }
+        for (ammo, mut text) in &mut ammo_texts {
+            if ammo.0 == number {
+                text.sections[0].value = match values.heat {
+                    Some(_) if values.overheated => "OVERHEATED".to_string(),
+                    Some(heat) => format!("Heat {heat}%"),
+                    None => format!("{} / {}", values.rounds, values.capacity),
+                };
+            }
+        }
+    }
+}
+
+/// Shows the health of the boss, if one is alive.
+fn update_boss_bar(
+    bosses: Query<&Health, With<Boss>>,
+    mut bar: Query<&mut Visibility, With<BossBar>>,
+    mut fill: Query<&mut Style, With<BossBarFill>>,
+    mut shown: Local<Option<f32>>,
+) {
+    let health_fraction = bosses
+        .iter()
+        .next()
+        .map(|health| health.current / health.max);
+    if *shown == health_fraction {
+        return;
+    }
+    *shown = health_fraction;
+
+    for mut visibility in &mut bar {
+        visibility.is_visible = health_fraction.is_some();
+    }
+    let Some(health_fraction) = health_fraction else { return };
+    for mut style in &mut fill {
+        style.size.width = Val::Percent(100.0 * health_fraction);
+    }
+}
+
+fn update_score_text(score: Res<Score>, mut score_text: Query<&mut Text, With<ScoreText>>) {
+    if !score.is_changed() {
+        return;
+    }
+    for mut text in &mut score_text {
+        text.sections[0].value = format!("Score: {}", score.0);
+    }
+}
+
+/// Shows the combo once there's more than one kill in it.
+fn update_combo_text(
+    combo: Res<Combo>,
+    tuning: Res<Tuning>,
+    mut combo_text: Query<&mut Text, With<ComboText>>,
+    mut shown: Local<u32>,
+) {
+    // The timer ticks every frame, only changes to the count are shown
+    if combo.count == *shown {
+        return;
+    }
+    *shown = combo.count;
+    for mut text in &mut combo_text {
+        text.sections[0].value = if combo.count > 1 {
+            format!("Combo {} (x{:.1})", combo.count, combo.multiplier(&tuning))
+        } else {
+            String::new()
+        };
+    }
+}
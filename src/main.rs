@@ -4,40 +4,126 @@ use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_inspector_egui_rapier::InspectableRapierPlugin;
 use bevy_rapier2d::prelude::*;
 
+use self::arena::ArenaPlugin;
+use self::audio::GameAudioPlugin;
+use self::background::BackgroundPlugin;
+use self::boss::BossPlugin;
 use self::bullet::BulletPlugin;
 use self::camera::GameCameraPlugin;
+use self::damage_numbers::DamageNumbersPlugin;
+use self::debug::DebugGizmosPlugin;
+use self::diagnostics::DiagnosticsOverlayPlugin;
+use self::difficulty::DifficultyPlugin;
+use self::enemy::EnemyPlugin;
+use self::fixed::FixedTimestepPlugin;
+use self::game_time::GameTimePlugin;
+use self::health::HealthPlugin;
+use self::hud::HudPlugin;
+use self::melee::MeleePlugin;
+use self::minimap::MinimapPlugin;
 use self::mouse::MousePositionPlugin;
+use self::pickup::PickupPlugin;
 use self::player::PlayerPlugin;
+use self::replay::ReplayPlugin;
+use self::rng::RngPlugin;
+use self::save::SavePlugin;
+use self::score::ScorePlugin;
+use self::settings::SettingsPlugin;
+use self::spatial::SpatialIndexPlugin;
+use self::state::GameStatePlugin;
+use self::stress::StressTestPlugin;
+use self::trajectory::TrajectoryPreviewPlugin;
+use self::tuning::TuningPlugin;
+use self::turret::TurretPlugin;
 use self::wall::WallPlugin;
+use self::weapon::WeaponPlugin;
 
+mod arena;
+mod audio;
+mod background;
+mod boss;
 mod bullet;
 mod camera;
+mod collision;
+mod damage_numbers;
+mod debug;
+mod diagnostics;
+mod difficulty;
+mod enemy;
+mod fixed;
+mod game_time;
+#[cfg(test)]
+mod headless;
+mod health;
+mod hot_reload;
+mod hud;
+mod melee;
+mod minimap;
 mod mouse;
+mod pickup;
 mod player;
+mod replay;
+mod rng;
+mod save;
+mod score;
+mod settings;
+mod spatial;
+mod state;
+mod stress;
+mod trajectory;
+mod tuning;
+mod turret;
 mod wall;
+mod weapon;
 
 fn main() {
+    let settings = SettingsPlugin::load();
     App::new()
         .add_plugins(DefaultPlugins.set(bevy::window::WindowPlugin {
-            window: WindowDescriptor {
-                width: 1200.,
-                height: 800.,
-                position: WindowPosition::Centered,
-                title: "Rust game!".into(),
-                resizable: false,
-                ..default()
-            },
+            window: settings.window_descriptor(),
             ..default()
         }))
-        .add_plugin(RapierDebugRenderPlugin::default())
+        .add_plugin(settings)
+        .add_plugin(RapierDebugRenderPlugin {
+            // Toggled with F2
+            enabled: cfg!(debug_assertions),
+            ..default()
+        })
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
         .add_plugin(InspectableRapierPlugin)
         .add_plugin(HanabiPlugin)
         .add_plugin(WorldInspectorPlugin)
+        .add_plugin(GameStatePlugin)
+        .add_plugin(RngPlugin)
+        .add_plugin(DifficultyPlugin)
+        .add_plugin(TuningPlugin)
+        .add_plugin(ArenaPlugin)
+        .add_plugin(GameTimePlugin)
+        .add_plugin(FixedTimestepPlugin)
+        .add_plugin(GameAudioPlugin)
         .add_plugin(GameCameraPlugin)
-        .add_plugin(PlayerPlugin)
-        .add_plugin(BulletPlugin)
+        .add_plugin(BackgroundPlugin)
+        .add_plugin(PlayerPlugin::default())
+        .add_plugin(WeaponPlugin)
+        .add_plugin(MeleePlugin)
+        .add_plugin(TurretPlugin)
+        .add_plugin(BulletPlugin::default())
+        .add_plugin(TrajectoryPreviewPlugin)
         .add_plugin(WallPlugin)
+        .add_plugin(PickupPlugin)
+        .add_plugin(EnemyPlugin)
+        .add_plugin(SpatialIndexPlugin)
+        .add_plugin(BossPlugin)
+        .add_plugin(HealthPlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(DamageNumbersPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_plugin(ScorePlugin)
         .add_plugin(MousePositionPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(DebugGizmosPlugin)
+        .add_plugin(DiagnosticsOverlayPlugin)
+        .add_plugin(StressTestPlugin)
         .run();
 }
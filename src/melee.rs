@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::audio::SoundEffects;
+use crate::bullet::{FadeOut, Lifetime};
+use crate::enemy::{Enemy, Knockback};
+use crate::game_time::GameTime;
+use crate::health::{DamageDealt, Health, Invulnerable};
+use crate::player::{aim_upper_body, find_muzzle, muzzle, Action, AimTarget, Muzzle, Player, Roll};
+use crate::state::{GameState, GameplayEntity};
+
+/// Lets the player slash at enemies right in front of them, for when they're out of ammo or
+/// swarmed.
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(melee.after(aim_upper_body)),
+        );
+    }
+}
+
+/// How far from the player's center the slash reaches.
+const MELEE_RANGE: f32 = 90.0;
+/// Angle of the sector in front of the player that the slash covers.
+const MELEE_ARC: f32 = 2.0 * PI / 3.0;
+const MELEE_DAMAGE: f32 = 25.0;
+/// Seconds between two slashes.
+const MELEE_COOLDOWN: f32 = 0.5;
+/// Speed enemies are knocked back at, which they then lose over a moment.
+const MELEE_KNOCKBACK: f32 = 700.0;
+/// Seconds the slash stays visible for.
+const SLASH_LIFETIME: f32 = 0.15;
+const SLASH_ALPHA: f32 = 0.8;
+
+/// Time until the player can slash again.
+#[derive(Component, Default)]
+pub struct MeleeCooldown(pub f32);
+
+/// Damages and knocks back every enemy within a sector in front of the player, where they're
+/// aiming.
+fn melee(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &Transform,
+            &mut MeleeCooldown,
+            &Roll,
+            &AimTarget,
+        ),
+        With<Player>,
+    >,
+    children: Query<&Children>,
+    muzzles: Query<&GlobalTransform, With<Muzzle>>,
+    mut enemies: Query<(&mut Health, &GlobalTransform), (With<Enemy>, Without<Invulnerable>)>,
+    rapier: Res<RapierContext>,
+    audio: Res<Audio>,
+    sounds: Res<SoundEffects>,
+    time: Res<GameTime>,
+    mut damaged: EventWriter<DamageDealt>,
+) {
+    for (entity, actions, tf, mut cooldown, roll, aim) in &mut player {
+        cooldown.0 = (cooldown.0 - time.delta_seconds()).max(0.0);
+        if !actions.just_pressed(Action::Melee) || cooldown.0 > 0.0 || roll.is_busy() {
+            continue;
+        }
+        let Some(muzzle_tf) = find_muzzle(entity, &children, &muzzles) else { continue };
+        cooldown.0 = MELEE_COOLDOWN;
+
+        let pos = tf.translation.xy();
+        let dir = muzzle(muzzle_tf, aim.point).1;
+        let mut hit = Vec::new();
+        rapier.intersections_with_shape(
+            pos,
+            0.0,
+            &Collider::ball(MELEE_RANGE),
+            QueryFilter::new().exclude_collider(entity),
+            |target| {
+                hit.push(target);
+                true
+            },
+        );
+        for target in hit {
+            let Ok((mut health, target_tf)) = enemies.get_mut(target) else { continue };
+            let offset = target_tf.translation().xy() - pos;
+            // Enemies right on top of the player are hit whichever way they're facing
+            if offset.length() > f32::EPSILON && offset.angle_between(dir).abs() > MELEE_ARC / 2.0 {
+                continue;
+            }
+            health.damage(MELEE_DAMAGE);
+            damaged.send(DamageDealt {
+                target,
+                point: target_tf.translation().xy(),
+                amount: MELEE_DAMAGE,
+                crit: false,
+            });
+            let away = offset.try_normalize().unwrap_or(dir);
+            commands
+                .entity(target)
+                .insert(Knockback(MELEE_KNOCKBACK * away));
+        }
+
+        sounds.play(&audio, &sounds.impact, 0.5);
+        spawn_slash(&mut commands, pos, dir);
+    }
+}
+
+/// Draws a streak across the sector that was slashed, fading out quickly.
+fn spawn_slash(commands: &mut Commands, pos: Vec2, dir: Vec2) {
+    commands.spawn((
+        Name::new("Slash"),
+        GameplayEntity,
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(1.0, 1.0, 1.0, SLASH_ALPHA),
+                custom_size: Some(Vec2::new(6.0, MELEE_RANGE * 1.2)),
+                ..default()
+            },
+            transform: Transform {
+                translation: (pos + 0.7 * MELEE_RANGE * dir).extend(0.5),
+                rotation: Quat::from_rotation_z(dir.y.atan2(dir.x)),
+                ..default()
+            },
+            ..default()
+        },
+        Lifetime(SLASH_LIFETIME),
+        FadeOut {
+            duration: SLASH_LIFETIME,
+            alpha: SLASH_ALPHA,
+        },
+    ));
+}
@@ -0,0 +1,148 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::math::Isometry;
+
+use crate::arena::ArenaBounds;
+use crate::enemy::Enemy;
+use crate::player::Player;
+use crate::wall::{BoundaryWall, Wall};
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_minimap)
+            .add_system(add_markers)
+            .add_system(update_markers.after(add_markers));
+    }
+}
+
+/// Width and height of the minimap in pixels.
+const MINIMAP_SIZE: f32 = 200.0;
+const DOT_SIZE: f32 = 6.0;
+/// Thin walls are still drawn at least this wide.
+const MIN_WALL_SIZE: f32 = 2.0;
+
+#[derive(Component)]
+struct Minimap;
+
+/// Shows where `target` is on the minimap.
+#[derive(Component)]
+struct MinimapMarker {
+    target: Entity,
+    size: Vec2,
+}
+
+/// Where on the minimap, from its top left corner, the world position `pos` is. The minimap
+/// shows the whole arena, anything outside of it is clamped to the edge.
+fn to_minimap(pos: Vec2, bounds: &ArenaBounds) -> Vec2 {
+    let normalized = ((pos - bounds.min) / bounds.size()).clamp(Vec2::ZERO, Vec2::ONE);
+    // UI coordinates grow downwards
+    MINIMAP_SIZE * Vec2::new(normalized.x, 1.0 - normalized.y)
+}
+
+fn spawn_minimap(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Minimap"),
+        Minimap,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..default()
+        },
+    ));
+}
+
+fn spawn_marker(
+    commands: &mut Commands,
+    minimap: Entity,
+    target: Entity,
+    color: Color,
+    size: Vec2,
+) {
+    let marker = commands
+        .spawn((
+            Name::new("Minimap marker"),
+            MinimapMarker { target, size },
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+                    ..default()
+                },
+                background_color: color.into(),
+                ..default()
+            },
+        ))
+        .id();
+    commands.entity(minimap).add_child(marker);
+}
+
+fn add_markers(
+    mut commands: Commands,
+    minimap: Query<Entity, With<Minimap>>,
+    players: Query<Entity, Added<Player>>,
+    enemies: Query<Entity, Added<Enemy>>,
+    walls: Query<(Entity, &Collider, &Transform), (Added<Wall>, Without<BoundaryWall>)>,
+    bounds: Res<ArenaBounds>,
+) {
+    let Ok(minimap) = minimap.get_single() else { return };
+
+    // Walls first so that moving things are drawn on top of them
+    let scale = MINIMAP_SIZE / bounds.size();
+    for (wall, collider, tf) in &walls {
+        // Markers can't be rotated, angled walls are shown as their bounding box instead
+        let angle = tf.rotation.to_euler(EulerRot::ZYX).0;
+        let extents = collider
+            .raw
+            .compute_aabb(&Isometry::rotation(angle))
+            .extents();
+        let size = (Vec2::new(extents.x, extents.y) * scale).max(Vec2::splat(MIN_WALL_SIZE));
+        spawn_marker(&mut commands, minimap, wall, Color::BLUE, size);
+    }
+    for enemy in &enemies {
+        spawn_marker(
+            &mut commands,
+            minimap,
+            enemy,
+            Color::RED,
+            Vec2::splat(DOT_SIZE),
+        );
+    }
+    for player in &players {
+        spawn_marker(
+            &mut commands,
+            minimap,
+            player,
+            Color::WHITE,
+            Vec2::splat(1.5 * DOT_SIZE),
+        );
+    }
+}
+
+fn update_markers(
+    mut commands: Commands,
+    mut markers: Query<(Entity, &MinimapMarker, &mut Style)>,
+    targets: Query<&Transform>,
+    bounds: Res<ArenaBounds>,
+) {
+    for (entity, marker, mut style) in &mut markers {
+        let Ok(tf) = targets.get(marker.target) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        let pos = to_minimap(tf.translation.xy(), &bounds) - marker.size / 2.0;
+        style.position.left = Val::Px(pos.x);
+        style.position.top = Val::Px(pos.y);
+    }
+}
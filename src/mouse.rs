@@ -1,19 +1,231 @@
+use bevy::math::Vec4Swizzles;
 use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use leafwing_input_manager::prelude::*;
+use leafwing_input_manager::user_input::InputKind;
+
+use crate::player::{Action, AimTarget, Player};
 
 pub struct MousePositionPlugin;
 
 impl Plugin for MousePositionPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MousePos>()
+            .init_resource::<HoveredCamera>()
             .add_system_to_stage(CoreStage::PreUpdate, update_mouse_pos);
     }
 }
 
+/// Where in the world the cursor is. Gameplay goes by each player's [`AimTarget`] instead.
 #[derive(Resource, Default)]
 pub struct MousePos(pub Vec2);
 
-fn update_mouse_pos(windows: Res<Windows>, mut mouse_pos: ResMut<MousePos>) {
-    let window = windows.get_primary().unwrap();
-    let Some(mpos) = window.cursor_position() else { return };
-    *mouse_pos = MousePos(mpos - Vec2::new(window.width(), window.height()) / 2.0);
+/// The camera whose viewport the cursor is in, `MousePos` is where it points in that camera's
+/// view.
+#[derive(Resource, Default, Deref)]
+pub struct HoveredCamera(pub Option<Entity>);
+
+/// Converts a cursor position, in logical pixels from the bottom left of a window of
+/// `window_size` logical pixels, to a point in the world seen by a camera with the given
+/// transform and projection. This works for any window size or scale factor, as long as the
+/// projection is the one the camera renders that window with.
+pub fn cursor_to_world(
+    cursor: Vec2,
+    window_size: Vec2,
+    camera_tf: &GlobalTransform,
+    projection: Mat4,
+) -> Vec2 {
+    let ndc = 2.0 * cursor / window_size - Vec2::ONE;
+    let world = camera_tf.compute_matrix() * projection.inverse() * ndc.extend(0.0).extend(1.0);
+    world.xy() / world.w
+}
+
+/// The part of `window` a camera renders to, as its bottom left corner and size in logical
+/// pixels.
+fn logical_viewport(camera: &Camera, window: &Window) -> (Vec2, Vec2) {
+    let window_size = Vec2::new(window.width(), window.height());
+    let Some(viewport) = &camera.viewport else { return (Vec2::ZERO, window_size) };
+
+    let scale = window.scale_factor() as f32;
+    let pos = viewport.physical_position.as_vec2() / scale;
+    let size = viewport.physical_size.as_vec2() / scale;
+    // Viewports are positioned from the top left, cursor positions from the bottom left
+    (Vec2::new(pos.x, window_size.y - pos.y - size.y), size)
+}
+
+/// Whether anything in `input_map` is on the mouse, which makes it the map of the player who aims
+/// with the cursor.
+pub fn uses_mouse(input_map: &InputMap<Action>) -> bool {
+    input_map.iter().any(|(inputs, _)| {
+        inputs.iter().any(|input| {
+            matches!(
+                input,
+                UserInput::Single(InputKind::Mouse(_) | InputKind::MouseWheel(_))
+            )
+        })
+    })
+}
+
+/// Finds where in the world the cursor is, and points the [`AimTarget`] of the player on the
+/// mouse at it unless they're aiming with a stick.
+pub fn update_mouse_pos(
+    windows: Res<Windows>,
+    cameras: Query<(Entity, &Camera, &GlobalTransform)>,
+    mut players: Query<(&mut AimTarget, &InputMap<Action>), With<Player>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut mouse_pos: ResMut<MousePos>,
+    mut hovered: ResMut<HoveredCamera>,
+) {
+    let moved = cursor_moved.iter().count() > 0;
+    for (entity, camera, camera_tf) in &cameras {
+        let window = match camera.target {
+            RenderTarget::Window(id) => windows.get(id),
+            RenderTarget::Image(_) => None,
+        };
+        let Some(window) = window else { continue };
+        let Some(cursor) = window.cursor_position() else { continue };
+
+        let (corner, size) = logical_viewport(camera, window);
+        let local = cursor - corner;
+        if local.cmplt(Vec2::ZERO).any() || local.cmpgt(size).any() {
+            continue;
+        }
+        *mouse_pos = MousePos(cursor_to_world(
+            local,
+            size,
+            camera_tf,
+            camera.projection_matrix(),
+        ));
+        hovered.0 = Some(entity);
+        for (mut aim, input_map) in &mut players {
+            if uses_mouse(input_map) {
+                aim.follow_cursor(mouse_pos.0, moved);
+            }
+        }
+        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::camera::{CameraProjection, Viewport};
+    use bevy::window::WindowId;
+
+    use super::*;
+
+    const CAMERA_POS: Vec2 = Vec2::new(50.0, -20.0);
+
+    /// Where `cursor` points in the world through `camera`, the way [`update_mouse_pos`] finds it.
+    fn world_point(camera: &Camera, window: &Window, cursor: Vec2) -> Vec2 {
+        let (corner, size) = logical_viewport(camera, window);
+        // Cameras are rendered with a projection of their logical viewport size
+        let mut projection = OrthographicProjection::default();
+        projection.update(size.x, size.y);
+        let camera_tf = GlobalTransform::from_translation(CAMERA_POS.extend(999.9));
+        cursor_to_world(
+            cursor - corner,
+            size,
+            &camera_tf,
+            projection.get_projection_matrix(),
+        )
+    }
+
+    fn window(physical_size: UVec2, scale_factor: f64) -> Window {
+        Window::new(
+            WindowId::primary(),
+            &WindowDescriptor::default(),
+            physical_size.x,
+            physical_size.y,
+            scale_factor,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn cursor_maps_to_world_for_any_window_size() {
+        for size in [
+            UVec2::new(800, 600),
+            UVec2::new(1920, 1080),
+            UVec2::new(300, 900),
+        ] {
+            let window = window(size, 1.0);
+            let half = size.as_vec2() / 2.0;
+            let center = world_point(&Camera::default(), &window, half);
+            assert!(center.abs_diff_eq(CAMERA_POS, 1e-3), "{size}: {center}");
+            let top_right = world_point(&Camera::default(), &window, size.as_vec2());
+            assert!(
+                top_right.abs_diff_eq(CAMERA_POS + half, 1e-3),
+                "{size}: {top_right}"
+            );
+            let bottom_left = world_point(&Camera::default(), &window, Vec2::ZERO);
+            assert!(
+                bottom_left.abs_diff_eq(CAMERA_POS - half, 1e-3),
+                "{size}: {bottom_left}"
+            );
+        }
+    }
+
+    #[test]
+    fn cursor_maps_to_world_for_any_scale_factor() {
+        for scale_factor in [1.0, 1.5, 2.0] {
+            let window = window(UVec2::new(1200, 900), scale_factor);
+            // Cursor positions are logical, so this is always 100 pixels in from the corner
+            let cursor = Vec2::new(100.0, 100.0);
+            let half = Vec2::new(1200.0, 900.0) / scale_factor as f32 / 2.0;
+            let point = world_point(&Camera::default(), &window, cursor);
+            let expected = CAMERA_POS - half + cursor;
+            assert!(point.abs_diff_eq(expected, 1e-3), "{scale_factor}: {point}");
+        }
+    }
+
+    #[test]
+    fn cursor_maps_to_world_in_a_viewport() {
+        for scale_factor in [1.0, 2.0] {
+            let window = window(UVec2::new(1600, 800), scale_factor);
+            let logical = Vec2::new(1600.0, 800.0) / scale_factor as f32;
+            // The right half of the window, and the top left quarter
+            let right = Camera {
+                viewport: Some(Viewport {
+                    physical_position: UVec2::new(800, 0),
+                    physical_size: UVec2::new(800, 800),
+                    ..default()
+                }),
+                ..default()
+            };
+            let top_left = Camera {
+                viewport: Some(Viewport {
+                    physical_position: UVec2::ZERO,
+                    physical_size: UVec2::new(800, 400),
+                    ..default()
+                }),
+                ..default()
+            };
+
+            let center = Vec2::new(0.75, 0.5) * logical;
+            let point = world_point(&right, &window, center);
+            assert!(
+                point.abs_diff_eq(CAMERA_POS, 1e-3),
+                "{scale_factor}: {point}"
+            );
+            let center = Vec2::new(0.25, 0.75) * logical;
+            let point = world_point(&top_left, &window, center);
+            assert!(
+                point.abs_diff_eq(CAMERA_POS, 1e-3),
+                "{scale_factor}: {point}"
+            );
+
+            // The top right corner of the top left quarter
+            let corner = Vec2::new(0.5, 1.0) * logical;
+            let point = world_point(&top_left, &window, corner);
+            let expected = CAMERA_POS + Vec2::new(0.25, 0.25) * logical;
+            assert!(point.abs_diff_eq(expected, 1e-3), "{scale_factor}: {point}");
+        }
+    }
+
+    #[test]
+    fn only_player_one_aims_with_the_mouse() {
+        assert!(uses_mouse(&Action::player_one()));
+        assert!(!uses_mouse(&Action::player_two()));
+    }
 }
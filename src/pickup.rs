@@ -0,0 +1,266 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_hanabi::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bullet::Lifetime;
+use crate::collision::pickup_groups;
+use crate::game_time::GameTime;
+use crate::health::Health;
+use crate::hot_reload::load_ron_or;
+use crate::player::Player;
+use crate::settings::GraphicsQuality;
+use crate::state::{GameState, GameplayEntity};
+use crate::weapon::{Loadout, Magazine};
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EnemyDrops::load(DROP_TABLE_PATH))
+            .add_startup_system(setup_pickup_effect)
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_pickups))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(collect_pickups)
+                    .with_system(animate_pickups),
+            );
+    }
+}
+
+/// Where designers can tune what enemies drop, see [`DropTable`] for the format.
+const DROP_TABLE_PATH: &str = "assets/drops.ron";
+const PICKUP_RADIUS: f32 = 15.0;
+const BOB_HEIGHT: f32 = 5.0;
+/// Bobs per second.
+const BOB_RATE: f32 = 1.5;
+/// Radians per second.
+const SPIN_RATE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PickupKind {
+    /// Heals this much health.
+    Health(f32),
+    /// Refills the magazine of the weapon being held.
+    Ammo,
+    /// Switches to the weapon at this index of the loadout, with a full magazine.
+    Weapon(usize),
+}
+
+impl PickupKind {
+    fn color(&self) -> Color {
+        match self {
+            PickupKind::Health(_) => Color::GREEN,
+            PickupKind::Ammo => Color::GOLD,
+            PickupKind::Weapon(_) => Color::SILVER,
+        }
+    }
+}
+
+/// What an enemy may leave behind when it dies, e.g.
+///
+/// ```ron
+/// (
+///     chance: 0.3,
+///     drops: [(Ammo, 3.0), (Health(20.0), 2.0), (Weapon(1), 1.0)],
+/// )
+/// ```
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct DropTable {
+    /// Probability of dropping anything at all.
+    pub chance: f32,
+    /// Possible drops with their relative weights.
+    pub drops: Vec<(PickupKind, f32)>,
+}
+
+impl Default for DropTable {
+    fn default() -> Self {
+        Self {
+            chance: 0.25,
+            drops: vec![
+                (PickupKind::Ammo, 4.0),
+                (PickupKind::Health(15.0), 3.0),
+                (PickupKind::Weapon(1), 1.0),
+                (PickupKind::Weapon(2), 1.0),
+                (PickupKind::Weapon(3), 0.5),
+                (PickupKind::Weapon(4), 1.0),
+                (PickupKind::Weapon(5), 1.0),
+                (PickupKind::Weapon(6), 0.5),
+                (PickupKind::Weapon(7), 0.5),
+            ],
+        }
+    }
+}
+
+impl DropTable {
+    /// Replaces a drop chance that isn't a number with the built-in one, as rolling it would
+    /// panic.
+    fn validated(mut self) -> Self {
+        if self.chance.is_nan() {
+            let chance = Self::default().chance;
+            warn!("Drop chance isn't a number, using {chance} instead");
+            self.chance = chance;
+        }
+        self
+    }
+
+    /// Picks what to drop, if anything.
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<PickupKind> {
+        if !rng.gen_bool(self.chance.clamp(0.0, 1.0) as f64) {
+            return None;
+        }
+        let weights = WeightedIndex::new(self.drops.iter().map(|&(_, weight)| weight)).ok()?;
+        Some(self.drops[weights.sample(rng)].0)
+    }
+}
+
+/// The drop table given to newly spawned enemies.
+#[derive(Resource, Debug, Clone, Default, Deref)]
+pub struct EnemyDrops(pub DropTable);
+
+impl EnemyDrops {
+    /// Reads the table from `path`, falling back to the built-in one if it's missing or invalid.
+    fn load(path: &str) -> Self {
+        Self(load_ron_or(path, "default drop table", DropTable::default).validated())
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Pickup {
+    pub kind: PickupKind,
+}
+
+/// The visible part of a pickup, animated separately so the sensor stays put.
+#[derive(Component)]
+struct PickupSprite;
+
+#[derive(Resource)]
+struct PickupEffect(Handle<EffectAsset>);
+
+pub fn spawn_pickup(commands: &mut Commands, pos: Vec2, kind: PickupKind) {
+    commands
+        .spawn((
+            Name::new("Pickup"),
+            Pickup { kind },
+            GameplayEntity,
+            SpatialBundle::from_transform(Transform::from_translation(pos.extend(0.0))),
+            Collider::ball(PICKUP_RADIUS),
+            Sensor,
+            pickup_groups(),
+            ActiveEvents::COLLISION_EVENTS,
+            ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_FIXED,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                PickupSprite,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: kind.color(),
+                        custom_size: Some(Vec2::splat(PICKUP_RADIUS * 1.5)),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn spawn_pickups(mut commands: Commands) {
+    spawn_pickup(
+        &mut commands,
+        Vec2::new(-300.0, 200.0),
+        PickupKind::Health(25.0),
+    );
+    spawn_pickup(&mut commands, Vec2::new(300.0, 200.0), PickupKind::Ammo);
+}
+
+/// Collects pickups as soon as a player starts touching them.
+fn collect_pickups(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    mut player: Query<(&mut Health, &mut Loadout, &mut Magazine), With<Player>>,
+    pickups: Query<(&Pickup, &Transform)>,
+    effect: Res<PickupEffect>,
+) {
+    // Two players can touch the same pickup at once, only the first gets it
+    let mut collected = HashSet::default();
+    for collision in collisions.iter() {
+        let CollisionEvent::Started(e1, e2, _) = *collision else { continue };
+        let (collector, entity) = match (player.contains(e1), player.contains(e2)) {
+            (true, _) => (e1, e2),
+            (_, true) => (e2, e1),
+            _ => continue,
+        };
+        // Enemies and walls the player touches aren't pickups
+        let Ok((pickup, tf)) = pickups.get(entity) else { continue };
+        if !collected.insert(entity) {
+            continue;
+        }
+        let Ok((mut health, mut loadout, mut magazine)) = player.get_mut(collector) else {
+            continue;
+        };
+
+        match pickup.kind {
+            PickupKind::Health(amount) => health.heal(amount),
+            PickupKind::Ammo => magazine.rounds = magazine.capacity,
+            PickupKind::Weapon(index) => loadout.equip_full(index, &mut magazine),
+        }
+        commands.entity(entity).despawn_recursive();
+        commands.spawn((
+            Name::new("Pickup particles"),
+            GameplayEntity,
+            SpatialBundle::from_transform(*tf),
+            ParticleEffect::new(effect.0.clone()).with_z_layer_2d(Some(0.2)),
+            Lifetime(1.0),
+        ));
+    }
+}
+
+fn animate_pickups(mut sprites: Query<&mut Transform, With<PickupSprite>>, time: Res<GameTime>) {
+    let t = time.elapsed_seconds();
+    for mut tf in &mut sprites {
+        tf.translation.y = BOB_HEIGHT * (t * BOB_RATE * TAU).sin();
+        tf.rotation = Quat::from_rotation_z(t * SPIN_RATE);
+    }
+}
+
+fn setup_pickup_effect(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    quality: Res<GraphicsQuality>,
+) {
+    let mut gradient = Gradient::new();
+    gradient.add_key(0.0, Vec4::new(1.0, 1.0, 0.8, 1.0));
+    gradient.add_key(1.0, Vec4::new(1.0, 1.0, 0.8, 0.0));
+
+    let pop = effects.add(
+        EffectAsset {
+            name: "Pickup pop".into(),
+            capacity: quality.capacity(256),
+            spawner: Spawner::once(quality.particles(30.0).into(), true),
+            ..default()
+        }
+        .init(InitPositionCircleModifier {
+            radius: PICKUP_RADIUS,
+            dimension: ShapeDimension::Surface,
+            ..default()
+        })
+        .init(InitVelocityCircleModifier {
+            speed: Value::Uniform((50.0, 150.0)),
+            ..default()
+        })
+        .init(InitLifetimeModifier {
+            lifetime: Value::Single(0.3),
+        })
+        .render(SizeOverLifetimeModifier {
+            gradient: Gradient::constant(Vec2::splat(2.0)),
+        })
+        .render(ColorOverLifetimeModifier { gradient }),
+    );
+    commands.insert_resource(PickupEffect(pop));
+}
@@ -1,208 +1,1551 @@
 use std::f32::consts::{PI, TAU};
 
+use bevy::asset::LoadState;
+use bevy::ecs::system::Command;
 use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
-use bevy::sprite::MaterialMesh2dBundle;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy_hanabi::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::buttonlike::MouseWheelDirection;
 use leafwing_input_manager::prelude::*;
 use leafwing_input_manager::user_input::InputKind;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::bullet::{Bullet, BulletEffects};
-use crate::mouse::MousePos;
+use crate::arena::ArenaBounds;
+use crate::audio::SoundEffects;
+use crate::bullet::{fire_bullets, BulletStats, FadeOut, FireBullet, Lifetime, Shooter};
+use crate::collision::player_groups;
+use crate::enemy::wall_filter;
+use crate::fixed::{FixedStep, FixedUpdateStage, Interpolated, Simulation};
+use crate::game_time::GameTime;
+use crate::health::{Faction, Health, Invulnerable};
+use crate::melee::MeleeCooldown;
+use crate::rng::GameRng;
+use crate::state::{GameState, GameplayEntity};
+use crate::tuning::{override_tuning, Tuning};
+use crate::wall::{push_out_of_walls, Level, Patrol};
+use crate::weapon::{Charge, FireMode, Heat, Loadout, Magazine, Reloading, WeaponEffects};
 
-pub struct PlayerPlugin;
+/// The player and their controls. Walking and dashing speed come from the [`Tuning`] unless set
+/// here, e.g. `PlayerPlugin::default().with_walk_speed(250.0)`. With `--two-players` a second
+/// player joins on the other side of the keyboard, see [`Action::player_two`].
+#[derive(Default)]
+pub struct PlayerPlugin {
+    walk_speed: Option<f32>,
+    dash_speed: Option<f32>,
+}
+
+impl PlayerPlugin {
+    pub fn with_walk_speed(mut self, speed: f32) -> Self {
+        self.walk_speed = Some(speed);
+        self
+    }
+
+    pub fn with_dash_speed(mut self, speed: f32) -> Self {
+        self.dash_speed = Some(speed);
+        self
+    }
+}
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
+        if let Some(speed) = self.walk_speed {
+            override_tuning(app, move |tuning| tuning.walk_speed = speed);
+        }
+        if let Some(speed) = self.dash_speed {
+            override_tuning(app, move |tuning| tuning.dash_speed = speed);
+        }
+        let two_players = std::env::args().skip(1).any(|arg| arg == "--two-players");
         app.add_plugin(InputManagerPlugin::<Action>::default())
-            .add_startup_system(spawn_player)
-            .add_system(move_player)
-            .add_system(update_player_pos.after(move_player))
-            .add_system(orient_player.after(update_player_pos))
-            .add_system(orient_legs.after(orient_player))
-            .add_system(shoot);
+            .insert_resource(PlayerCount(if two_players { 2 } else { 1 }))
+            .add_event::<PlayerDied>()
+            .init_resource::<LegSettings>()
+            .init_resource::<SpawnProtection>()
+            .add_startup_system(load_player_sprites)
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_player))
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                SystemSet::new()
+                    .label(Simulation)
+                    .with_system(move_player)
+                    .with_system(update_player_pos.after(move_player)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(orient_player)
+                    .with_system(update_aim)
+                    .with_system(aim_upper_body.after(orient_player).after(update_aim))
+                    .with_system(orient_legs.after(orient_player))
+                    .with_system(animate_legs)
+                    .with_system(leave_footprints.after(orient_legs).after(animate_legs))
+                    .with_system(breathe)
+                    .with_system(pick_player_art)
+                    .with_system(switch_weapon)
+                    .with_system(reload.after(switch_weapon))
+                    .with_system(
+                        shoot
+                            .after(reload)
+                            .after(aim_upper_body)
+                            .before(fire_bullets),
+                    )
+                    .with_system(show_charge_glow.after(shoot))
+                    .with_system(start_dash.after(aim_upper_body))
+                    .with_system(spawn_afterimages.after(start_dash))
+                    .with_system(start_roll.after(start_dash)),
+            )
+            .add_system(player_death);
     }
 }
 
-#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
-enum Action {
+pub const PLAYER_RADIUS: f32 = 40.0;
+/// Distance walked between two footstep sounds.
+const STEP_DISTANCE: f32 = 60.0;
+/// Seconds of holding the trigger it takes to fully charge a shot.
+const FULL_CHARGE_TIME: f32 = 1.5;
+/// How far in front of the upper body the tip of the gun is, just outside the player's collider.
+const MUZZLE_OFFSET: f32 = PLAYER_RADIUS + 10.0;
+/// How far the right stick has to be pushed before it takes over aiming from the cursor.
+const STICK_DEADZONE: f32 = 0.3;
+/// How far out aiming with the stick aims, about where the cursor usually is.
+const STICK_AIM_DISTANCE: f32 = 300.0;
+/// Radians per second the player turns at by default.
+const TURN_SPEED: f32 = PI;
+const DASH_DURATION: f32 = 0.2;
+/// Time from the start of a dash until the player can dash again.
+const DASH_COOLDOWN: f32 = 1.0;
+/// Time between two afterimages left behind while dashing.
+const AFTERIMAGE_INTERVAL: f32 = 0.03;
+const AFTERIMAGE_LIFETIME: f32 = 0.25;
+const AFTERIMAGE_ALPHA: f32 = 0.4;
+/// Time after a dodge roll during which the player can't act.
+const ROLL_RECOVERY: f32 = 0.15;
+/// Time from the start of a dodge roll until the player can roll again.
+const ROLL_COOLDOWN: f32 = 0.8;
+/// Size the body sprite is drawn at, about as wide as the primitive body it replaces.
+const BODY_SPRITE_SIZE: Vec2 = Vec2::splat(100.0);
+/// Number of frames in the walk cycle sprite sheet, the first one is also the idle pose.
+const LEG_FRAMES: usize = 8;
+const LEG_FRAME_SIZE: Vec2 = Vec2::new(50.0, 100.0);
+/// Distance walked during one full walk cycle.
+const WALK_CYCLE_DISTANCE: f32 = 2.0 * STEP_DISTANCE;
+/// How far to the side of the player's center footprints are left.
+const FOOTPRINT_OFFSET: f32 = 12.0;
+const FOOTPRINT_LIFETIME: f32 = 4.0;
+const FOOTPRINT_ALPHA: f32 = 0.3;
+/// Where player two starts relative to player one.
+const PLAYER_TWO_OFFSET: Vec2 = Vec2::new(150.0, 0.0);
+/// Once there are this many footprints, the oldest ones are removed to make room for new ones.
+const MAX_FOOTPRINTS: usize = 64;
+/// Breaths per second while standing still.
+const BREATH_RATE: f32 = 0.4;
+/// How much the upper body grows and shrinks while breathing.
+const BREATH_SCALE: f32 = 0.04;
+/// How far the upper body bobs back and forth while breathing.
+const BREATH_BOB: f32 = 1.5;
+/// How quickly, per second, breathing fades in after stopping and out after starting to move.
+const BREATH_EASE: f32 = 3.0;
+
+#[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
     Up,
     Down,
     Left,
     Right,
     Shoot,
+    Reload,
+    Aim,
+    /// Aiming with the right stick, which way it's pushed is the way to aim.
+    Look,
+    SelectWeapon1,
+    SelectWeapon2,
+    SelectWeapon3,
+    SelectWeapon4,
+    SelectWeapon5,
+    SelectWeapon6,
+    SelectWeapon7,
+    SelectWeapon8,
+    NextWeapon,
+    PreviousWeapon,
+    Dash,
+    Roll,
+    Melee,
+    PlaceTurret,
 }
 
 impl Action {
-    fn player_one() -> InputMap<Self> {
+    pub fn player_one() -> InputMap<Self> {
         let mut input_map = InputMap::new([
             (KeyCode::W, Action::Up),
             (KeyCode::A, Action::Left),
             (KeyCode::S, Action::Down),
             (KeyCode::D, Action::Right),
             (KeyCode::F, Action::Shoot),
+            (KeyCode::R, Action::Reload),
+            (KeyCode::Key1, Action::SelectWeapon1),
+            (KeyCode::Key2, Action::SelectWeapon2),
+            (KeyCode::Key3, Action::SelectWeapon3),
+            (KeyCode::Key4, Action::SelectWeapon4),
+            (KeyCode::Key5, Action::SelectWeapon5),
+            (KeyCode::Key6, Action::SelectWeapon6),
+            (KeyCode::Key7, Action::SelectWeapon7),
+            (KeyCode::Key8, Action::SelectWeapon8),
+            (KeyCode::Space, Action::Dash),
+            (KeyCode::LShift, Action::Roll),
+            (KeyCode::V, Action::Melee),
+            (KeyCode::G, Action::PlaceTurret),
         ]);
         input_map.insert(InputKind::Mouse(MouseButton::Left), Action::Shoot);
+        input_map.insert(InputKind::Mouse(MouseButton::Right), Action::Aim);
+        input_map.insert(DualAxis::right_stick(), Action::Look);
+        input_map.insert(
+            InputKind::MouseWheel(MouseWheelDirection::Up),
+            Action::NextWeapon,
+        );
+        input_map.insert(
+            InputKind::MouseWheel(MouseWheelDirection::Down),
+            Action::PreviousWeapon,
+        );
         input_map
     }
+
+    /// Player two plays on the right of the keyboard: the arrow keys walk, the number pad aims
+    /// the way the right stick does, and the keys around them do the rest. There's no mouse or
+    /// controller, those are player one's.
+    pub fn player_two() -> InputMap<Self> {
+        let mut input_map = InputMap::new([
+            (KeyCode::Up, Action::Up),
+            (KeyCode::Left, Action::Left),
+            (KeyCode::Down, Action::Down),
+            (KeyCode::Right, Action::Right),
+            (KeyCode::Numpad0, Action::Shoot),
+            (KeyCode::NumpadEnter, Action::Aim),
+            (KeyCode::Numpad9, Action::Reload),
+            (KeyCode::PageUp, Action::NextWeapon),
+            (KeyCode::PageDown, Action::PreviousWeapon),
+            (KeyCode::RShift, Action::Dash),
+            (KeyCode::RControl, Action::Roll),
+            (KeyCode::Numpad7, Action::Melee),
+            (KeyCode::Numpad3, Action::PlaceTurret),
+        ]);
+        input_map.insert(
+            VirtualDPad {
+                up: KeyCode::Numpad8.into(),
+                down: KeyCode::Numpad5.into(),
+                left: KeyCode::Numpad4.into(),
+                right: KeyCode::Numpad6.into(),
+            },
+            Action::Look,
+        );
+        input_map
+    }
+}
+
+#[derive(Component)]
+pub struct Player;
+
+/// Which player this is, 0 for player one and 1 for player two.
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Deref)]
+pub struct PlayerNumber(pub usize);
+
+/// How many players the game is played with, 2 with `--two-players` and 1 otherwise.
+#[derive(Resource, Clone, Copy, Deref)]
+pub struct PlayerCount(pub usize);
+
+/// Turns the legs the way the player is walking, see [`orient_legs`].
+#[derive(Component, Default)]
+struct LowerBody {
+    /// The angle the legs should be at in the world.
+    target: f32,
+    /// The angle they're at, turning towards `target`.
+    angle: f32,
 }
 
 #[derive(Component)]
-struct Player;
+struct ChargeGlow;
+
+/// Turns to aim at the cursor independently of the rest of the body, carrying the gun with it.
+#[derive(Component, Default)]
+struct UpperBody {
+    /// The angle it's aiming at in the world, kept separately so it doesn't get dragged along as
+    /// the rest of the body turns.
+    aim: f32,
+    /// How deeply it breathes, easing from 0 while walking to 1 when standing still.
+    breath: f32,
+}
 
+/// The tip of the gun, bullets are fired from here along its -x axis.
 #[derive(Component)]
-struct LowerBody;
+pub struct Muzzle;
 
+/// Sprites and meshes making up the player's body, copied to leave afterimages when dashing.
 #[derive(Component)]
-struct UpperBody;
+struct BodyPart;
+
+/// Distance walked since the last footstep sound.
+#[derive(Component, Default)]
+struct Walked(f32);
+
+/// Time since the last afterimage was left behind while dashing.
+#[derive(Component, Default)]
+struct SinceAfterimage(f32);
+
+/// All of `entity`'s descendants, for finding the parts of a player's body.
+pub fn descendants(entity: Entity, children: &Query<&Children>) -> Vec<Entity> {
+    let mut found = Vec::new();
+    let mut stack = vec![entity];
+    while let Some(entity) = stack.pop() {
+        if let Ok(children) = children.get(entity) {
+            found.extend(children.iter());
+            stack.extend(children.iter());
+        }
+    }
+    found
+}
 
+/// The muzzle of `player`'s gun.
+pub fn find_muzzle<'a>(
+    player: Entity,
+    children: &Query<&Children>,
+    muzzles: &'a Query<&GlobalTransform, With<Muzzle>>,
+) -> Option<&'a GlobalTransform> {
+    muzzles.iter_many(descendants(player, children)).next()
+}
+
+/// Radians per second the player turns towards where they're walking, and their upper body
+/// towards where they're aiming.
 #[derive(Component, Deref, DerefMut)]
-struct MoveDir(Vec2);
+pub struct TurnSpeed(pub f32);
 
-fn shoot(
+impl Default for TurnSpeed {
+    fn default() -> Self {
+        Self(TURN_SPEED)
+    }
+}
+
+/// Where the player is aiming, as a point in the world and the direction from the player
+/// towards it. The player on the mouse aims at the cursor, see
+/// [`update_mouse_pos`](crate::mouse::update_mouse_pos), players on a controller aim along the
+/// right stick and keep aiming the same way when it's let go. Whichever was used last wins.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct AimTarget {
+    pub point: Vec2,
+    pub dir: Vec2,
+    /// Whether the stick was used more recently than the mouse.
+    stick: bool,
+}
+
+impl Default for AimTarget {
+    fn default() -> Self {
+        // The player faces along -x
+        Self {
+            point: Vec2::ZERO,
+            dir: Vec2::NEG_X,
+            stick: false,
+        }
+    }
+}
+
+impl AimTarget {
+    /// Whether the player is aiming at the cursor rather than with the stick.
+    pub fn on_cursor(&self) -> bool {
+        !self.stick
+    }
+
+    /// Aims at the cursor at `point`, unless the player has been aiming with the stick since the
+    /// cursor last `moved`.
+    pub fn follow_cursor(&mut self, point: Vec2, moved: bool) {
+        if moved {
+            self.stick = false;
+        }
+        if !self.stick {
+            self.point = point;
+        }
+    }
+}
+
+/// How far the player moves during the current fixed step.
+#[derive(Component, Deref, DerefMut)]
+pub struct MoveDir(Vec2);
+
+/// The player's character art. Until it has loaded, or if it's missing, the player is drawn
+/// with primitive shapes instead.
+#[derive(Resource)]
+struct PlayerSprites {
+    /// Drawn facing left, the way the gun points.
+    body: Handle<Image>,
+    legs: Handle<Image>,
+    /// The walk cycle frames in `legs`.
+    leg_frames: Handle<TextureAtlas>,
+}
+
+/// Which version of the player's art a body part belongs to, only one of them is shown.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum PlayerArt {
+    Sprite,
+    Primitive,
+}
+
+/// Seconds the player is invulnerable for after spawning.
+#[derive(Resource, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct SpawnProtection(pub f32);
+
+impl Default for SpawnProtection {
+    fn default() -> Self {
+        Self(2.0)
+    }
+}
+
+/// How the legs turn to face the way the player is walking.
+#[derive(Resource, Debug, Clone)]
+pub struct LegSettings {
+    /// Largest angle the legs can be twisted away from the body before they turn back.
+    pub max_twist: f32,
+    /// How far the legs turn back towards the body when twisted too far.
+    pub snap_angle: f32,
+    /// Radians per second the legs turn at, infinite to turn instantly.
+    pub turn_speed: f32,
+}
+
+impl Default for LegSettings {
+    fn default() -> Self {
+        Self {
+            max_twist: PI / 4.0,
+            snap_angle: PI / 4.0,
+            turn_speed: f32::INFINITY,
+        }
+    }
+}
+
+/// Plays the walk cycle on the legs' sprite sheet at a speed matching the player's.
+#[derive(Component, Default)]
+struct WalkCycle {
+    /// How far into the cycle the legs are, from 0 to 1.
+    phase: f32,
+    /// The foot that left the last footprint, `None` while standing still.
+    last_foot: Option<bool>,
+}
+
+impl WalkCycle {
+    /// The left foot is put down during the first half of the cycle, the right during the second.
+    fn left_foot(&self) -> bool {
+        self.phase < 0.5
+    }
+}
+
+#[derive(Component)]
+struct Footprint;
+
+/// A short burst of speed in a fixed direction.
+#[derive(Component, Default)]
+pub struct Dash {
+    /// Time left of the current dash, zero when not dashing.
+    pub time_left: f32,
+    /// Time until the player can dash again.
+    pub cooldown: f32,
+    dir: Vec2,
+}
+
+impl Dash {
+    pub fn is_active(&self) -> bool {
+        self.time_left > 0.0
+    }
+}
+
+/// A dodge roll a set distance in a fixed direction, invulnerable for most of it, after which
+/// the player needs a moment to recover before they can act again.
+#[derive(Component, Default)]
+pub struct Roll {
+    /// Time left of the current roll, zero when not rolling.
+    pub time_left: f32,
+    /// Time left to recover from the last roll, zero once recovered.
+    pub recovery: f32,
+    /// Time until the player can roll again.
+    pub cooldown: f32,
+    dir: Vec2,
+    speed: f32,
+}
+
+impl Roll {
+    pub fn is_active(&self) -> bool {
+        self.time_left > 0.0
+    }
+
+    /// Whether the player is rolling or still recovering from it, and can't do anything else.
+    pub fn is_busy(&self) -> bool {
+        self.is_active() || self.recovery > 0.0
+    }
+}
+
+pub struct PlayerDied;
+
+/// Where bullets fired from a [`Muzzle`] with the given transform at `target` start, and which
+/// way they fly. They start where the muzzle was last drawn, so they always leave the visible
+/// barrel, and fly straight at the target however far the upper body still has to turn. With the
+/// target right on the muzzle they fly the way the muzzle is pointing.
+pub fn muzzle(tf: &GlobalTransform, target: Vec2) -> (Vec3, Vec2) {
+    let pos = tf.translation();
+    let dir = (target - pos.xy())
+        .try_normalize()
+        .unwrap_or(-tf.right().xy());
+    (pos, dir)
+}
+
+/// Scales the bullet up with how charged the shot is, up to triple damage at full charge.
+fn charged_stats(stats: BulletStats, charge: f32) -> BulletStats {
+    BulletStats {
+        speed: stats.speed * (1.0 + charge),
+        damage: stats.damage * (1.0 + 2.0 * charge),
+        size: stats.size * (1.0 + 2.0 * charge),
+        ..stats
+    }
+}
+
+fn player_death(
     mut commands: Commands,
-    player: Query<(&Transform, &ActionState<Action>), With<Player>>,
-    bullet_effects: Res<BulletEffects>,
+    player: Query<(Entity, &Health), With<Player>>,
+    mut died: EventWriter<PlayerDied>,
 ) {
-    let (tf, actions) = player.single();
-
-    if actions.just_pressed(Action::Shoot) {
-        Bullet::spawn(
-            &mut commands,
-            tf.translation - 50.0 * tf.right(),
-            -tf.right().xy(),
-            bullet_effects.trail.clone(),
-        );
+    for (entity, health) in &player {
+        if health.is_dead() {
+            commands.entity(entity).despawn_recursive();
+            died.send(PlayerDied);
+        }
     }
 }
 
-fn orient_legs(
-    player: Query<(&Transform, &MoveDir), With<Player>>,
-    mut legs: Query<&mut Transform, (Without<Player>, Without<UpperBody>, With<LowerBody>)>,
-    mut angle: Local<f32>,
+fn switch_weapon(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &mut Loadout,
+            &mut Magazine,
+            &mut Charge,
+        ),
+        With<Player>,
+    >,
 ) {
-    let (player_tf, move_dir) = player.single();
-    let mut legs_tf = legs.single_mut();
+    for (entity, actions, mut loadout, mut magazine, mut charge) in &mut player {
+        let count = loadout.weapons.len();
+        let selected = [
+            Action::SelectWeapon1,
+            Action::SelectWeapon2,
+            Action::SelectWeapon3,
+            Action::SelectWeapon4,
+            Action::SelectWeapon5,
+            Action::SelectWeapon6,
+            Action::SelectWeapon7,
+            Action::SelectWeapon8,
+        ]
+        .into_iter()
+        .position(|action| actions.just_pressed(action));
+        let index = if let Some(index) = selected {
+            index
+        } else if actions.just_pressed(Action::NextWeapon) {
+            (loadout.current + 1) % count
+        } else if actions.just_pressed(Action::PreviousWeapon) {
+            (loadout.current + count - 1) % count
+        } else {
+            continue;
+        };
 
-    if **move_dir != Vec2::ZERO {
-        *angle = move_dir.y.atan2(move_dir.x);
-        if move_dir.dot(-player_tf.right().xy()) < 0.0 {
-            *angle = (*angle + PI).rem_euclid(TAU);
+        if index != loadout.current && index < count {
+            loadout.switch_to(index, &mut magazine);
+            **charge = 0.0;
+            commands.entity(entity).remove::<Reloading>();
         }
     }
+}
 
-    let leg_diff = (*angle - player_tf.rotation.to_euler(EulerRot::ZYX).0).rem_euclid(TAU) - PI;
-    if leg_diff.abs() > PI / 4.0 {
-        *angle -= PI / 4.0 * leg_diff.signum();
+fn reload(
+    mut commands: Commands,
+    player: Query<(Entity, &ActionState<Action>, &Loadout, &Magazine), Without<Reloading>>,
+) {
+    for (entity, actions, loadout, magazine) in &player {
+        if actions.just_pressed(Action::Reload) && magazine.rounds < magazine.capacity {
+            let duration = loadout.current().reload_time;
+            commands
+                .entity(entity)
+                .insert(Reloading(Timer::from_seconds(duration, TimerMode::Once)));
+        }
     }
-
-    legs_tf.rotation = player_tf.rotation.inverse() * Quat::from_rotation_z(*angle);
 }
 
-fn orient_player(
-    mut player: Query<&mut Transform, With<Player>>,
-    time: Res<Time>,
-    mpos: Res<MousePos>,
+fn shoot(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &Transform,
+            &MoveDir,
+            &mut Loadout,
+            &mut Magazine,
+            &mut Heat,
+            &mut Charge,
+            &Roll,
+            &AimTarget,
+            Option<&Reloading>,
+        ),
+        With<Player>,
+    >,
+    children: Query<&Children>,
+    muzzles: Query<&GlobalTransform, With<Muzzle>>,
+    mut fire: EventWriter<FireBullet>,
+    weapon_effects: Option<Res<WeaponEffects>>,
+    audio: Res<Audio>,
+    sounds: Res<SoundEffects>,
+    mut rng: ResMut<GameRng>,
+    time: Res<GameTime>,
+    fixed: Res<FixedStep>,
+    tuning: Res<Tuning>,
 ) {
-    let mut tf = player.single_mut();
+    for player in &mut player {
+        let (
+            entity,
+            actions,
+            tf,
+            move_dir,
+            mut loadout,
+            mut magazine,
+            mut heat,
+            mut charge,
+            roll,
+            aim,
+            reloading,
+        ) = player;
+
+        loadout.cooldown = (loadout.cooldown - time.delta_seconds()).max(0.0);
+        if roll.is_busy() {
+            continue;
+        }
+        let heat_per_shot = loadout.current().heat_per_shot;
+        let locked = match heat_per_shot {
+            Some(_) => heat.overheated,
+            None => reloading.is_some() || magazine.rounds == 0,
+        };
+        if locked {
+            // Reloading, running dry or overheating cuts a burst short
+            loadout.current_mut().burst_left = 0;
+            continue;
+        }
+        if loadout.cooldown > 0.0 {
+            continue;
+        }
 
-    let look_dir = tf.translation.xy() - mpos.0;
-    let target_angle = Quat::from_rotation_z(look_dir.y.atan2(look_dir.x));
+        let weapon = loadout.current().clone();
+        let stats = match weapon.fire_mode {
+            FireMode::Semi if actions.just_pressed(Action::Shoot) => weapon.bullet,
+            FireMode::Auto if actions.pressed(Action::Shoot) => weapon.bullet,
+            FireMode::Charged if actions.pressed(Action::Shoot) => {
+                **charge = (**charge + time.delta_seconds() / FULL_CHARGE_TIME).min(1.0);
+                continue;
+            }
+            FireMode::Charged if actions.just_released(Action::Shoot) => {
+                let stats = charged_stats(weapon.bullet, **charge);
+                **charge = 0.0;
+                stats
+            }
+            FireMode::Burst(burst) => {
+                let current = loadout.current_mut();
+                if burst.cancel_on_release && !actions.pressed(Action::Shoot) {
+                    current.burst_left = 0;
+                }
+                if current.burst_left == 0 {
+                    if !actions.just_pressed(Action::Shoot) {
+                        continue;
+                    }
+                    current.burst_left = burst.rounds;
+                }
+                current.burst_left -= 1;
+                weapon.bullet
+            }
+            _ => continue,
+        };
 
-    // Limit speed of rotation
-    const ANGULAR_SPEED: f32 = 180.0 / 180.0 * PI;
-    let movement = (ANGULAR_SPEED / tf.rotation.angle_between(target_angle) * time.delta_seconds())
-        .clamp(0.0, 1.0);
+        let overheated = match heat_per_shot {
+            Some(amount) => heat.add(amount),
+            None => {
+                magazine.rounds -= 1;
+                false
+            }
+        };
+        loadout.cooldown = match weapon.fire_mode {
+            FireMode::Burst(burst) if loadout.current().burst_left > 0 => burst.interval,
+            _ => 1.0 / weapon.fire_rate,
+        };
+        sounds.play(&audio, &sounds.gunshot, 0.6);
 
-    tf.rotation = tf.rotation.slerp(target_angle, movement.sqrt());
+        let Some(muzzle_tf) = find_muzzle(entity, &children, &muzzles) else { continue };
+        let (pos, dir) = muzzle(muzzle_tf, aim.point);
+        let shooter = Shooter {
+            entity,
+            faction: Faction::Player,
+            // The player moves by `MoveDir` every step
+            velocity: if tuning.inherit_velocity {
+                **move_dir / fixed.delta_seconds()
+            } else {
+                Vec2::ZERO
+            },
+            pos: tf.translation.xy(),
+            radius: PLAYER_RADIUS,
+        };
+        if let Some(effects) = weapon_effects.as_ref().filter(|_| overheated) {
+            commands.spawn((
+                Name::new("Steam particles"),
+                GameplayEntity,
+                SpatialBundle::from_transform(Transform::from_translation(pos)),
+                ParticleEffect::new(effects.steam.clone()).with_z_layer_2d(Some(0.2)),
+                Lifetime(5.0),
+            ));
+        }
+        for _ in 0..weapon.pellets {
+            let angle = rng.gen_range(-weapon.spread / 2.0..=weapon.spread / 2.0);
+            fire.send(FireBullet {
+                origin: pos,
+                dir: Vec2::from_angle(angle).rotate(dir),
+                stats,
+                shooter,
+            });
+        }
+    }
 }
 
-fn move_player(
-    mut player: Query<(&mut MoveDir, &ActionState<Action>), With<Player>>,
-    time: Res<Time>,
+fn start_dash(
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &MoveDir,
+            &AimTarget,
+            &mut Dash,
+            &Roll,
+        ),
+        With<Player>,
+    >,
+    children: Query<&Children>,
+    muzzles: Query<&GlobalTransform, With<Muzzle>>,
 ) {
-    let (mut move_dir, actions) = player.single_mut();
-    let mut dir = Vec2::ZERO;
-    if actions.pressed(Action::Up) {
-        dir += Vec2::Y;
+    for (entity, actions, move_dir, aim, mut dash, roll) in &mut player {
+        if !actions.just_pressed(Action::Dash) || dash.cooldown > 0.0 || roll.is_busy() {
+            continue;
+        }
+        let Some(muzzle_tf) = find_muzzle(entity, &children, &muzzles) else { continue };
+
+        // Dash where the player is walking, or where they're aiming when standing still
+        let dir = if **move_dir == Vec2::ZERO {
+            muzzle(muzzle_tf, aim.point).1
+        } else {
+            move_dir.normalize()
+        };
+        *dash = Dash {
+            time_left: DASH_DURATION,
+            cooldown: DASH_COOLDOWN,
+            dir,
+        };
     }
-    if actions.pressed(Action::Down) {
-        dir += Vec2::NEG_Y;
+}
+
+/// Starts a dodge roll the same way as a dash, making the player invulnerable for the start of it.
+fn start_roll(
+    mut commands: Commands,
+    mut player: Query<
+        (
+            Entity,
+            &ActionState<Action>,
+            &MoveDir,
+            &AimTarget,
+            &Dash,
+            &mut Roll,
+            Option<&Invulnerable>,
+        ),
+        With<Player>,
+    >,
+    children: Query<&Children>,
+    muzzles: Query<&GlobalTransform, With<Muzzle>>,
+    tuning: Res<Tuning>,
+) {
+    for (entity, actions, move_dir, aim, dash, mut roll, invulnerable) in &mut player {
+        if !actions.just_pressed(Action::Roll)
+            || roll.cooldown > 0.0
+            || roll.is_busy()
+            || dash.is_active()
+        {
+            continue;
+        }
+        let Some(muzzle_tf) = find_muzzle(entity, &children, &muzzles) else { continue };
+
+        let dir = if **move_dir == Vec2::ZERO {
+            muzzle(muzzle_tf, aim.point).1
+        } else {
+            move_dir.normalize()
+        };
+        // A roll tuned to take no time at all still covers its distance, in a single step
+        let duration = tuning.roll_duration.max(f32::EPSILON);
+        *roll = Roll {
+            time_left: duration,
+            recovery: 0.0,
+            cooldown: ROLL_COOLDOWN,
+            dir,
+            speed: tuning.roll_distance / duration,
+        };
+        // Longer invulnerability, like spawn protection, isn't cut short
+        let iframes = tuning.roll_iframes.min(tuning.roll_duration);
+        if invulnerable.map_or(true, |invulnerable| {
+            invulnerable.0.remaining_secs() < iframes
+        }) {
+            commands
+                .entity(entity)
+                .insert(Invulnerable::from_seconds(iframes));
+        }
     }
-    if actions.pressed(Action::Left) {
-        dir += Vec2::NEG_X;
+}
+
+/// Leaves fading copies of the player's body behind for as long as a dash lasts.
+fn spawn_afterimages(
+    mut commands: Commands,
+    mut player: Query<(Entity, &Dash, &mut SinceAfterimage), With<Player>>,
+    children: Query<&Children>,
+    parts: Query<
+        (
+            &GlobalTransform,
+            Option<&Sprite>,
+            Option<&Mesh2dHandle>,
+            Option<&Handle<ColorMaterial>>,
+            Option<(&TextureAtlasSprite, &Handle<TextureAtlas>)>,
+            &Visibility,
+        ),
+        With<BodyPart>,
+    >,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<GameTime>,
+) {
+    for (entity, dash, mut since_last) in &mut player {
+        if !dash.is_active() {
+            // Start every dash with an afterimage right where it began
+            since_last.0 = AFTERIMAGE_INTERVAL;
+            continue;
+        }
+        since_last.0 += time.delta_seconds();
+        if since_last.0 < AFTERIMAGE_INTERVAL {
+            continue;
+        }
+        since_last.0 -= AFTERIMAGE_INTERVAL;
+
+        let body = descendants(entity, &children);
+        for (global_tf, sprite, mesh, material, atlas_sprite, visibility) in parts.iter_many(body) {
+            // The version of the art that isn't being used
+            if !visibility.is_visible {
+                continue;
+            }
+            let mut transform = global_tf.compute_transform();
+            // Behind the player
+            transform.translation.z -= 0.5;
+            let mut afterimage = commands.spawn((
+                Name::new("Afterimage"),
+                GameplayEntity,
+                Lifetime(AFTERIMAGE_LIFETIME),
+                FadeOut {
+                    duration: AFTERIMAGE_LIFETIME,
+                    alpha: AFTERIMAGE_ALPHA,
+                },
+            ));
+            if let Some(sprite) = sprite {
+                let mut sprite = sprite.clone();
+                sprite.color.set_a(AFTERIMAGE_ALPHA);
+                afterimage.insert(SpriteBundle {
+                    sprite,
+                    transform,
+                    ..default()
+                });
+            } else if let Some((sprite, atlas)) = atlas_sprite {
+                let mut sprite = sprite.clone();
+                sprite.color.set_a(AFTERIMAGE_ALPHA);
+                afterimage.insert(SpriteSheetBundle {
+                    sprite,
+                    texture_atlas: atlas.clone(),
+                    transform,
+                    ..default()
+                });
+            } else if let (Some(mesh), Some(material)) = (mesh, material) {
+                let mut color = materials.get(material).map_or(Color::WHITE, |m| m.color);
+                color.set_a(AFTERIMAGE_ALPHA);
+                afterimage.insert(MaterialMesh2dBundle {
+                    mesh: mesh.clone(),
+                    material: materials.add(ColorMaterial::from(color)),
+                    transform,
+                    ..default()
+                });
+            }
+        }
     }
-    if actions.pressed(Action::Right) {
-        dir += Vec2::X;
+}
+
+fn show_charge_glow(
+    player: Query<(Entity, &Charge), With<Player>>,
+    children: Query<&Children>,
+    mut glow: Query<(&mut Transform, &mut Visibility), With<ChargeGlow>>,
+) {
+    for (entity, charge) in &player {
+        let mut glow = glow.iter_many_mut(descendants(entity, &children));
+        while let Some((mut tf, mut visibility)) = glow.fetch_next() {
+            visibility.is_visible = **charge > 0.0;
+            tf.scale = Vec3::splat(1.0 + 4.0 * **charge);
+        }
     }
-    let speed = 200. * time.delta_seconds();
+}
 
-    dir = speed * dir.normalize_or_zero();
+fn load_player_sprites(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let legs = asset_server.load("sprites/legs.png");
+    let atlas = TextureAtlas::from_grid(legs.clone(), LEG_FRAME_SIZE, LEG_FRAMES, 1, None, None);
+    commands.insert_resource(PlayerSprites {
+        body: asset_server.load("sprites/body.png"),
+        legs,
+        leg_frames: atlases.add(atlas),
+    });
+}
 
-    **move_dir = dir;
+/// Shows the player's sprites once they've loaded, and the primitive art until then or for good
+/// if they can't be loaded.
+fn pick_player_art(
+    mut parts: Query<(&PlayerArt, &mut Visibility)>,
+    sprites: Res<PlayerSprites>,
+    asset_server: Res<AssetServer>,
+    mut warned: Local<bool>,
+) {
+    let load_state = asset_server.get_group_load_state([sprites.body.id(), sprites.legs.id()]);
+    if load_state == LoadState::Failed && !*warned {
+        warn!("Couldn't load the player's sprites, drawing them with primitive shapes instead");
+        *warned = true;
+    }
+    let shown = if load_state == LoadState::Loaded {
+        PlayerArt::Sprite
+    } else {
+        PlayerArt::Primitive
+    };
+    for (&art, mut visibility) in &mut parts {
+        let visible = art == shown;
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
 }
 
-fn update_player_pos(mut player: Query<(&mut Transform, &MoveDir), With<Player>>) {
-    let (mut tf, dir) = player.single_mut();
-    tf.translation += dir.extend(0.0);
+fn animate_legs(
+    player: Query<(Entity, &MoveDir), With<Player>>,
+    children: Query<&Children>,
+    mut legs: Query<(&mut WalkCycle, &mut TextureAtlasSprite)>,
+    fixed: Res<FixedStep>,
+    time: Res<GameTime>,
+) {
+    for (entity, move_dir) in &player {
+        // `MoveDir` is the distance moved during a single step
+        let speed = move_dir.length() / fixed.delta_seconds();
+        let mut legs = legs.iter_many_mut(descendants(entity, &children));
+        while let Some((mut cycle, mut sprite)) = legs.fetch_next() {
+            if speed == 0.0 {
+                cycle.phase = 0.0;
+                sprite.index = 0;
+                continue;
+            }
+            let phase = cycle.phase + speed * time.delta_seconds() / WALK_CYCLE_DISTANCE;
+            cycle.phase = phase.fract();
+            sprite.index = (cycle.phase * LEG_FRAMES as f32) as usize % LEG_FRAMES;
+        }
+    }
 }
 
-fn spawn_player(
+/// Leaves a footprint each time the walk cycle puts a foot down, on that foot's side.
+fn leave_footprints(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    player: Query<(Entity, &Transform, &MoveDir), With<Player>>,
+    children: Query<&Children>,
+    mut legs: Query<(&mut WalkCycle, &TextureAtlasSprite)>,
+    footprints: Query<(Entity, &Lifetime), With<Footprint>>,
 ) {
-    commands
-        .spawn((
-            Name::new("Player"),
-            Player,
-            SpatialBundle {
-                transform: Transform::from_xyz(0.0, 300.0, 0.0),
+    for (entity, tf, move_dir) in &player {
+        let mut legs = legs.iter_many_mut(descendants(entity, &children));
+        let Some((mut cycle, sprite)) = legs.fetch_next() else { continue };
+        if **move_dir == Vec2::ZERO {
+            cycle.last_foot = None;
+            continue;
+        }
+        let left = cycle.left_foot();
+        if cycle.last_foot.replace(left) == Some(left) {
+            continue;
+        }
+
+        if footprints.iter().count() >= MAX_FOOTPRINTS {
+            let oldest = footprints
+                .iter()
+                .min_by(|(_, a), (_, b)| a.0.total_cmp(&b.0));
+            if let Some((entity, _)) = oldest {
+                commands.entity(entity).despawn();
+            }
+        }
+
+        let dir = move_dir.normalize();
+        // A flipped sprite has its left foot on the right
+        let side = if left != sprite.flip_y {
+            dir.perp()
+        } else {
+            -dir.perp()
+        };
+        let pos = tf.translation.xy() + FOOTPRINT_OFFSET * side;
+        commands.spawn((
+            Name::new("Footprint"),
+            Footprint,
+            GameplayEntity,
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.0, 0.0, 0.0, FOOTPRINT_ALPHA),
+                    custom_size: Some(Vec2::new(14.0, 8.0)),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: pos.extend(-0.5),
+                    rotation: Quat::from_rotation_z(dir.y.atan2(dir.x)),
+                    ..default()
+                },
                 ..default()
             },
-            InputManagerBundle {
-                input_map: Action::player_one(),
-                ..default()
+            Lifetime(FOOTPRINT_LIFETIME),
+            FadeOut {
+                duration: FOOTPRINT_LIFETIME,
+                alpha: FOOTPRINT_ALPHA,
             },
-            MoveDir(Vec2::ZERO),
-        ))
-        .with_children(|parent| {
-            parent.spawn((
-                Name::new("Upper body"),
-                MaterialMesh2dBundle {
-                    mesh: meshes
-                        .add(Mesh::from(shape::RegularPolygon::new(50.0, 3)))
-                        .into(),
-                    material: materials.add(ColorMaterial::from(Color::PURPLE)),
-                    transform: Transform {
-                        rotation: Quat::from_rotation_z(PI / 2.0),
-                        translation: Vec3::new(0.0, 0.0, 0.1),
-                        ..default()
-                    },
+        ));
+    }
+}
+
+/// Makes the upper body breathe while standing still. This only touches the upper body's own
+/// transform, the player and its collider stay put.
+fn breathe(
+    player: Query<(Entity, &MoveDir), With<Player>>,
+    children: Query<&Children>,
+    mut upper_body: Query<(&mut Transform, &mut UpperBody)>,
+    time: Res<GameTime>,
+) {
+    let max_change = BREATH_EASE * time.delta_seconds();
+    let cycle = (time.elapsed_seconds() * BREATH_RATE * TAU).sin();
+    for (entity, move_dir) in &player {
+        let target = if **move_dir == Vec2::ZERO { 1.0 } else { 0.0 };
+        let mut upper_body = upper_body.iter_many_mut(descendants(entity, &children));
+        while let Some((mut tf, mut body)) = upper_body.fetch_next() {
+            body.breath += (target - body.breath).clamp(-max_change, max_change);
+            let breath = body.breath * cycle;
+            let scale = 1.0 + BREATH_SCALE * breath;
+            tf.scale = Vec3::new(scale, scale, 1.0);
+            tf.translation.x = BREATH_BOB * breath;
+        }
+    }
+}
+
+/// Turns the legs towards the angle they should be at, keeping track of the angle they're at in
+/// their [`LowerBody`]. While they point left the leg sprite is flipped vertically, so the art
+/// stays the right way up instead of being turned upside down.
+fn orient_legs(
+    player: Query<(Entity, &Transform, &MoveDir), With<Player>>,
+    children: Query<&Children>,
+    mut legs: Query<(&mut Transform, &mut LowerBody), Without<Player>>,
+    mut leg_sprites: Query<&mut TextureAtlasSprite, With<WalkCycle>>,
+    settings: Res<LegSettings>,
+    time: Res<GameTime>,
+) {
+    for (entity, player_tf, move_dir) in &player {
+        let body = descendants(entity, &children);
+        let mut lower_bodies = legs.iter_many_mut(&body);
+        let Some((mut legs_tf, mut lower_body)) = lower_bodies.fetch_next() else { continue };
+
+        if **move_dir != Vec2::ZERO {
+            lower_body.target = move_dir.y.atan2(move_dir.x);
+            if move_dir.dot(-player_tf.right().xy()) < 0.0 {
+                lower_body.target = (lower_body.target + PI).rem_euclid(TAU);
+            }
+        }
+
+        let player_angle = player_tf.rotation.to_euler(EulerRot::ZYX).0;
+        let leg_diff = (lower_body.target - player_angle).rem_euclid(TAU) - PI;
+        if leg_diff.abs() > settings.max_twist {
+            lower_body.target -= settings.snap_angle * leg_diff.signum();
+        }
+
+        // The shortest way around towards the target
+        let turn = (lower_body.target - lower_body.angle + PI).rem_euclid(TAU) - PI;
+        if settings.turn_speed.is_finite() {
+            let max_turn = settings.turn_speed * time.delta_seconds();
+            lower_body.angle += turn.clamp(-max_turn, max_turn);
+        } else {
+            lower_body.angle += turn;
+        }
+
+        legs_tf.rotation = player_tf.rotation.inverse() * Quat::from_rotation_z(lower_body.angle);
+        let pointing_left = lower_body.angle.cos() < 0.0;
+        let mut sprites = leg_sprites.iter_many_mut(&body);
+        while let Some(mut sprite) = sprites.fetch_next() {
+            if sprite.flip_y != pointing_left {
+                sprite.flip_y = pointing_left;
+            }
+        }
+    }
+}
+
+/// Turns from `angle` towards `target` the shortest way around, by at most `max_turn`.
+pub fn turn_towards(angle: f32, target: f32, max_turn: f32) -> f32 {
+    let turn = (target - angle + PI).rem_euclid(TAU) - PI;
+    angle + turn.clamp(-max_turn, max_turn)
+}
+
+/// Turns each player to face where they're walking, keeping the way they faced when standing
+/// still.
+pub fn orient_player(
+    mut player: Query<(&mut Transform, &MoveDir, &TurnSpeed), With<Player>>,
+    time: Res<GameTime>,
+) {
+    for (mut tf, move_dir, turn_speed) in &mut player {
+        if **move_dir == Vec2::ZERO {
+            continue;
+        }
+
+        // The player faces along -x
+        let target = (-move_dir.y).atan2(-move_dir.x);
+        let angle = tf.rotation.to_euler(EulerRot::ZYX).0;
+        let max_turn = **turn_speed * time.delta_seconds();
+        tf.rotation = Quat::from_rotation_z(turn_towards(angle, target, max_turn));
+    }
+}
+
+/// Moves each player's [`AimTarget`] out along their right stick while it's pushed, and along
+/// with the player while they keep aiming the same way after letting go of it.
+fn update_aim(
+    mut players: Query<(&Transform, &ActionState<Action>, &mut AimTarget), With<Player>>,
+) {
+    for (tf, actions, mut aim) in &mut players {
+        let pos = tf.translation.xy();
+        let stick = actions
+            .axis_pair(Action::Look)
+            .map_or(Vec2::ZERO, |axis| axis.xy());
+        if stick.length() > STICK_DEADZONE {
+            aim.stick = true;
+            aim.point = pos + STICK_AIM_DISTANCE * stick.normalize();
+        } else if aim.stick {
+            aim.point = pos + STICK_AIM_DISTANCE * aim.dir;
+        }
+
+        // With the cursor right on top of the player there's nowhere to look, keep aiming the
+        // same way
+        if let Some(dir) = (aim.point - pos).try_normalize() {
+            aim.dir = dir;
+        }
+    }
+}
+
+/// Turns each player's upper body the way they're aiming.
+pub fn aim_upper_body(
+    player: Query<(Entity, &Transform, &TurnSpeed, &AimTarget), With<Player>>,
+    children: Query<&Children>,
+    mut upper_body: Query<(&mut Transform, &mut UpperBody), Without<Player>>,
+    time: Res<GameTime>,
+) {
+    for (entity, tf, turn_speed, aim_target) in &player {
+        let mut upper_bodies = upper_body.iter_many_mut(descendants(entity, &children));
+        let Some((mut upper_body_tf, mut body)) = upper_bodies.fetch_next() else { continue };
+
+        // The upper body faces along -x
+        let look_dir = -aim_target.dir;
+        let max_turn = **turn_speed * time.delta_seconds();
+        body.aim = turn_towards(body.aim, look_dir.y.atan2(look_dir.x), max_turn);
+
+        upper_body_tf.rotation = tf.rotation.inverse() * Quat::from_rotation_z(body.aim);
+    }
+}
+
+fn move_player(
+    mut player: Query<
+        (
+            &mut MoveDir,
+            &mut Walked,
+            &mut Dash,
+            &mut Roll,
+            &ActionState<Action>,
+        ),
+        With<Player>,
+    >,
+    fixed: Res<FixedStep>,
+    tuning: Res<Tuning>,
+    audio: Res<Audio>,
+    sounds: Res<SoundEffects>,
+) {
+    for (mut move_dir, mut walked, mut dash, mut roll, actions) in &mut player {
+        dash.cooldown = (dash.cooldown - fixed.delta_seconds()).max(0.0);
+        roll.cooldown = (roll.cooldown - fixed.delta_seconds()).max(0.0);
+        if dash.is_active() {
+            dash.time_left = (dash.time_left - fixed.delta_seconds()).max(0.0);
+            **move_dir = tuning.dash_speed * fixed.delta_seconds() * dash.dir;
+            continue;
+        }
+        if roll.is_active() {
+            // The last step of the roll is cut short so it always covers the same distance
+            let step = roll.time_left.min(fixed.delta_seconds());
+            roll.time_left -= step;
+            if !roll.is_active() {
+                roll.recovery = ROLL_RECOVERY;
+            }
+            **move_dir = roll.speed * step * roll.dir;
+            continue;
+        }
+        if roll.recovery > 0.0 {
+            roll.recovery = (roll.recovery - fixed.delta_seconds()).max(0.0);
+            **move_dir = Vec2::ZERO;
+            continue;
+        }
+
+        let mut dir = Vec2::ZERO;
+        if actions.pressed(Action::Up) {
+            dir += Vec2::Y;
+        }
+        if actions.pressed(Action::Down) {
+            dir += Vec2::NEG_Y;
+        }
+        if actions.pressed(Action::Left) {
+            dir += Vec2::NEG_X;
+        }
+        if actions.pressed(Action::Right) {
+            dir += Vec2::X;
+        }
+        let speed = tuning.walk_speed * fixed.delta_seconds();
+
+        dir = speed * dir.normalize_or_zero();
+
+        walked.0 += dir.length();
+        if walked.0 >= STEP_DISTANCE {
+            walked.0 -= STEP_DISTANCE;
+            sounds.play(&audio, &sounds.footstep, 0.3);
+        }
+
+        **move_dir = dir;
+    }
+}
+
+/// Moves each player by its `MoveDir`, keeping it inside the arena. Patrolling walls push the
+/// player along, as Rapier doesn't let one kinematic body push another.
+fn update_player_pos(
+    mut player: Query<(&mut Transform, &MoveDir), With<Player>>,
+    patrols: Query<(&Collider, &GlobalTransform), With<Patrol>>,
+    rapier: Res<RapierContext>,
+    bounds: Res<ArenaBounds>,
+) {
+    for (mut tf, dir) in &mut player {
+        let pos = tf.translation.xy() + **dir;
+        let pos = push_out_of_walls(pos, PLAYER_RADIUS, &rapier, wall_filter(), &patrols);
+        let pos = bounds.clamp(pos, Vec2::splat(PLAYER_RADIUS));
+        tf.translation = pos.extend(tf.translation.z);
+    }
+}
+
+fn spawn_player(mut commands: Commands, level: Res<Level>, count: Res<PlayerCount>) {
+    let pos = level.player_spawn;
+    spawn_player_at(&mut commands, pos, PlayerNumber(0), Action::player_one());
+    if *count > 1 {
+        let pos = pos + PLAYER_TWO_OFFSET;
+        spawn_player_at(&mut commands, pos, PlayerNumber(1), Action::player_two());
+    }
+}
+
+/// Spawns player `number` at `pos`, controlled with `input_map`. Once the command has run
+/// there's a player with a fresh loadout, briefly protected by [`SpawnProtection`].
+pub fn spawn_player_at(
+    commands: &mut Commands,
+    pos: Vec2,
+    number: PlayerNumber,
+    input_map: InputMap<Action>,
+) {
+    commands.add(SpawnPlayer {
+        pos,
+        number,
+        input_map,
+    });
+}
+
+/// Spawns the player with everything it's made of, see [`spawn_player_at`].
+pub struct SpawnPlayer {
+    pub pos: Vec2,
+    pub number: PlayerNumber,
+    pub input_map: InputMap<Action>,
+}
+
+impl Command for SpawnPlayer {
+    fn write(self, world: &mut World) {
+        let torso_mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Mesh::from(shape::RegularPolygon::new(50.0, 3)));
+        let torso_material = world
+            .resource_mut::<Assets<ColorMaterial>>()
+            .add(ColorMaterial::from(Color::PURPLE));
+        let sprites = world.resource::<PlayerSprites>();
+        let (body, leg_frames) = (sprites.body.clone(), sprites.leg_frames.clone());
+        let protection = **world.resource::<SpawnProtection>();
+        let loadout = Loadout::new(world.resource::<Tuning>());
+        world
+            .spawn((
+                Name::new("Player"),
+                (Player, self.number),
+                GameplayEntity,
+                SpatialBundle {
+                    transform: Transform::from_translation(self.pos.extend(0.0)),
                     ..default()
                 },
-                UpperBody,
-            ));
-            parent
-                .spawn((Name::new("Lower body"), SpatialBundle::default(), LowerBody))
-                .with_children(|parent| {
-                    parent.spawn(SpriteBundle {
-                        sprite: Sprite {
-                            color: Color::GRAY,
-                            custom_size: Some(Vec2::new(25.0, 80.0)),
-                            ..default()
-                        },
-                        transform: Transform::from_xyz(12.5, 0.0, 0.0),
-                        ..default()
+                Interpolated::new(self.pos.extend(0.0)),
+                InputManagerBundle {
+                    input_map: self.input_map,
+                    ..default()
+                },
+                (MoveDir(Vec2::ZERO), Walked::default()),
+                AimTarget::default(),
+                (
+                    Dash::default(),
+                    SinceAfterimage::default(),
+                    Roll::default(),
+                    MeleeCooldown::default(),
+                ),
+                TurnSpeed::default(),
+                Health::new(100.0),
+                Invulnerable::from_seconds(protection),
+                Faction::Player,
+                (
+                    loadout.magazine(),
+                    loadout,
+                    Heat::default(),
+                    Charge::default(),
+                ),
+                (
+                    RigidBody::KinematicPositionBased,
+                    Collider::ball(PLAYER_RADIUS),
+                    player_groups(),
+                    ActiveEvents::COLLISION_EVENTS,
+                    ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_KINEMATIC,
+                ),
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn((
+                        Name::new("Upper body"),
+                        SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, 0.1)),
+                        UpperBody::default(),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Name::new("Torso"),
+                            MaterialMesh2dBundle {
+                                mesh: torso_mesh.into(),
+                                material: torso_material,
+                                transform: Transform::from_rotation(Quat::from_rotation_z(
+                                    PI / 2.0,
+                                )),
+                                ..default()
+                            },
+                            BodyPart,
+                            PlayerArt::Primitive,
+                        ));
+                        parent.spawn((
+                            Name::new("Torso sprite"),
+                            SpriteBundle {
+                                sprite: Sprite {
+                                    custom_size: Some(BODY_SPRITE_SIZE),
+                                    ..default()
+                                },
+                                texture: body,
+                                visibility: Visibility { is_visible: false },
+                                ..default()
+                            },
+                            BodyPart,
+                            PlayerArt::Sprite,
+                        ));
+                        parent
+                            .spawn((
+                                Name::new("Muzzle"),
+                                SpatialBundle::from_transform(Transform::from_xyz(
+                                    -MUZZLE_OFFSET,
+                                    0.0,
+                                    0.0,
+                                )),
+                                Muzzle,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Name::new("Charge glow"),
+                                    ChargeGlow,
+                                    SpriteBundle {
+                                        sprite: Sprite {
+                                            color: Color::rgba(0.6, 0.6, 1.0, 0.7),
+                                            custom_size: Some(Vec2::splat(4.0)),
+                                            ..default()
+                                        },
+                                        transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                                        visibility: Visibility { is_visible: false },
+                                        ..default()
+                                    },
+                                ));
+                            });
                     });
-                    parent.spawn(SpriteBundle {
-                        sprite: Sprite {
-                            color: Color::DARK_GRAY,
-                            custom_size: Some(Vec2::new(25.0, 100.0)),
-                            ..default()
-                        },
-                        transform: Transform::from_xyz(-12.5, 0.0, 0.0),
-                        ..default()
+                parent
+                    .spawn((
+                        Name::new("Lower body"),
+                        SpatialBundle::default(),
+                        LowerBody::default(),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Name::new("Legs"),
+                            SpriteSheetBundle {
+                                sprite: TextureAtlasSprite {
+                                    custom_size: Some(LEG_FRAME_SIZE),
+                                    ..default()
+                                },
+                                texture_atlas: leg_frames,
+                                visibility: Visibility { is_visible: false },
+                                ..default()
+                            },
+                            WalkCycle::default(),
+                            BodyPart,
+                            PlayerArt::Sprite,
+                        ));
+                        for (color, length, x) in
+                            [(Color::GRAY, 80.0, 12.5), (Color::DARK_GRAY, 100.0, -12.5)]
+                        {
+                            parent.spawn((
+                                Name::new("Leg"),
+                                SpriteBundle {
+                                    sprite: Sprite {
+                                        color,
+                                        custom_size: Some(Vec2::new(25.0, length)),
+                                        ..default()
+                                    },
+                                    transform: Transform::from_xyz(x, 0.0, 0.0),
+                                    ..default()
+                                },
+                                BodyPart,
+                                PlayerArt::Primitive,
+                            ));
+                        }
                     });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::wall_groups;
+    use crate::headless::{headless_app, run_fixed_steps};
+    use crate::wall::Wall;
+
+    /// The muzzle's transform with the player at `pos`, turned by `body_angle`, and its upper body
+    /// turned to `aim_angle` in the world.
+    fn muzzle_tf(pos: Vec2, body_angle: f32, aim_angle: f32) -> GlobalTransform {
+        let body = Transform::from_translation(pos.extend(0.0))
+            .with_rotation(Quat::from_rotation_z(body_angle));
+        let upper_body =
+            Transform::from_rotation(body.rotation.inverse() * Quat::from_rotation_z(aim_angle));
+        let muzzle = Transform::from_xyz(-MUZZLE_OFFSET, 0.0, 0.0);
+        GlobalTransform::from(body) * GlobalTransform::from(upper_body) * muzzle
+    }
+
+    #[test]
+    fn bullets_fly_from_the_muzzle_straight_at_the_target() {
+        let pos = Vec2::new(100.0, 50.0);
+        let target = Vec2::new(-300.0, 400.0);
+        for body_angle in [0.0, 1.0, -2.5] {
+            // However far the upper body still has to turn
+            for aim_angle in [0.0, PI / 2.0, PI, -PI / 3.0, 2.0] {
+                let tf = muzzle_tf(pos, body_angle, aim_angle);
+                let (origin, dir) = muzzle(&tf, target);
+                assert_eq!(origin, tf.translation());
+                let expected = (target - origin.xy()).normalize();
+                assert!(dir.abs_diff_eq(expected, 1e-5), "{aim_angle}: {dir}");
+            }
+        }
+    }
+
+    #[test]
+    fn bullets_fly_the_way_the_muzzle_points_with_the_target_on_it() {
+        for aim_angle in [0.0, PI / 2.0, PI, -PI / 3.0, 2.0] {
+            let tf = muzzle_tf(Vec2::new(100.0, 50.0), 1.0, aim_angle);
+            let (origin, dir) = muzzle(&tf, tf.translation().xy());
+            // The upper body faces along -x
+            let expected = -Vec2::from_angle(aim_angle);
+            assert!(dir.abs_diff_eq(expected, 1e-5), "{aim_angle}: {dir}");
+            assert!(origin.xy().distance(Vec2::new(100.0, 50.0)) > PLAYER_RADIUS);
+        }
+    }
+
+    #[test]
+    fn each_player_aims_their_own_upper_body() {
+        let mut app = headless_app();
+        app.add_system(aim_upper_body);
+        let aims = [Vec2::Y, Vec2::X];
+        let upper_bodies = aims.map(|dir| {
+            let mut upper_body = None;
+            app.world
+                .spawn((
+                    Player,
+                    TurnSpeed(1000.0),
+                    AimTarget { dir, ..default() },
+                    TransformBundle::default(),
+                ))
+                .with_children(|parent| {
+                    upper_body = Some(
+                        parent
+                            .spawn((UpperBody::default(), Transform::default()))
+                            .id(),
+                    );
                 });
+            upper_body.unwrap()
         });
+
+        run_fixed_steps(&mut app, 1);
+        for (upper_body, dir) in upper_bodies.into_iter().zip(aims) {
+            let tf = app.world.get::<Transform>(upper_body).unwrap();
+            // The upper body faces along -x
+            let facing = -(tf.rotation * Vec3::X).xy();
+            assert!(facing.abs_diff_eq(dir, 1e-4), "{facing} != {dir}");
+        }
+    }
+
+    #[test]
+    fn patrolling_wall_pushes_the_player() {
+        let mut app = headless_app();
+        app.add_system_to_stage(FixedUpdateStage, update_player_pos);
+        let patrol: Patrol = ron::from_str("(waypoints: [], speed: 0.0)").unwrap();
+        app.world.spawn((
+            Wall,
+            patrol,
+            Collider::cuboid(50.0, 50.0),
+            wall_groups(),
+            TransformBundle::default(),
+        ));
+        // Overlapping the wall's right edge, as if the wall had just moved into the player
+        let player = app
+            .world
+            .spawn((
+                Player,
+                MoveDir(Vec2::ZERO),
+                TransformBundle::from_transform(Transform::from_xyz(70.0, 10.0, 0.0)),
+            ))
+            .id();
+        app.add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0));
+        app.update();
+
+        run_fixed_steps(&mut app, 1);
+        let pos = app.world.get::<Transform>(player).unwrap().translation.xy();
+        let expected = Vec2::new(50.0 + PLAYER_RADIUS, 10.0);
+        assert!(pos.abs_diff_eq(expected, 0.01), "{pos}");
+    }
 }
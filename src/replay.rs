@@ -0,0 +1,178 @@
+use std::fs;
+use std::iter;
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use leafwing_input_manager::plugin::InputManagerSystem;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::fixed::FixedStep;
+use crate::game_time::GameTime;
+use crate::mouse::update_mouse_pos;
+use crate::player::{Action, AimTarget, Player, PlayerNumber};
+
+/// Records the players' input to a file with `--record <file>`, or plays it back instead of the
+/// live input with `--replay <file>`.
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            // Only these take a value, so a flag right before one of them mustn't swallow it
+            if arg != "--record" && arg != "--replay" {
+                continue;
+            }
+            let Some(path) = args.next() else { break };
+            match arg.as_str() {
+                "--record" => {
+                    app.insert_resource(Recorder {
+                        path: path.into(),
+                        frames: Vec::new(),
+                    })
+                    .add_system_to_stage(
+                        CoreStage::PreUpdate,
+                        record_input
+                            .after(InputManagerSystem::Update)
+                            .after(update_mouse_pos),
+                    )
+                    .add_system_to_stage(CoreStage::Last, save_recording);
+                }
+                "--replay" => match Replayer::load(path.into()) {
+                    Ok(replayer) => {
+                        app.insert_resource(replayer)
+                            .add_system(disable_live_input)
+                            .add_system_to_stage(
+                                CoreStage::PreUpdate,
+                                replay_input
+                                    .after(InputManagerSystem::Update)
+                                    .after(update_mouse_pos),
+                            );
+                    }
+                    Err(err) => error!("Couldn't load replay: {err}"),
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The input of a single frame. The first player's input is stored directly in it, so older
+/// recordings from before there could be two players still load.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RecordedInput {
+    pub pressed: Vec<Action>,
+    /// Where the player was aiming, kept under its old name so older recordings still load.
+    pub mouse_pos: [f32; 2],
+    /// Length of the frame in game time, so replaying it runs as many fixed steps as when it was
+    /// recorded, even if the time scale was changed.
+    pub delta: f32,
+    /// The input of the players after the first, in order of their [`PlayerNumber`].
+    #[serde(default)]
+    pub other_players: Vec<PlayerInput>,
+}
+
+/// What one player pressed during a frame and where they were aiming.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PlayerInput {
+    pub pressed: Vec<Action>,
+    pub aim: [f32; 2],
+}
+
+#[derive(Resource)]
+pub struct Recorder {
+    path: PathBuf,
+    frames: Vec<RecordedInput>,
+}
+
+#[derive(Resource)]
+pub struct Replayer {
+    frames: Vec<RecordedInput>,
+    next: usize,
+}
+
+impl Replayer {
+    fn load(path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let frames = ron::from_str(&fs::read_to_string(path)?)?;
+        Ok(Self { frames, next: 0 })
+    }
+}
+
+fn record_input(
+    mut recorder: ResMut<Recorder>,
+    player: Query<(&PlayerNumber, &ActionState<Action>, &AimTarget), With<Player>>,
+    time: Res<GameTime>,
+) {
+    let mut players: Vec<_> = player.iter().collect();
+    players.sort_by_key(|&(&number, ..)| number);
+    let mut inputs = players.into_iter().map(|(_, actions, aim)| PlayerInput {
+        pressed: actions.get_pressed(),
+        aim: aim.point.to_array(),
+    });
+    let first = inputs.next().unwrap_or_default();
+    recorder.frames.push(RecordedInput {
+        pressed: first.pressed,
+        mouse_pos: first.aim,
+        delta: time.delta_seconds(),
+        other_players: inputs.collect(),
+    });
+}
+
+fn save_recording(recorder: Res<Recorder>, mut exit: EventReader<AppExit>) {
+    if exit.iter().next().is_none() {
+        return;
+    }
+
+    let result = ron::to_string(&recorder.frames)
+        .map_err(|err| err.to_string())
+        .and_then(|ron| fs::write(&recorder.path, ron).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => info!(
+            "Saved {} frames of input to {}",
+            recorder.frames.len(),
+            recorder.path.display()
+        ),
+        Err(err) => error!("Couldn't save recording: {err}"),
+    }
+}
+
+/// Removing the input map stops the live input from touching the player's `ActionState`, so
+/// only the replayed input affects it.
+fn disable_live_input(mut commands: Commands, player: Query<Entity, Added<Player>>) {
+    for entity in &player {
+        commands.entity(entity).remove::<InputMap<Action>>();
+    }
+}
+
+fn replay_input(
+    mut replayer: ResMut<Replayer>,
+    mut player: Query<(&PlayerNumber, &mut ActionState<Action>, &mut AimTarget), With<Player>>,
+    mut fixed: ResMut<FixedStep>,
+) {
+    let Some(frame) = replayer.frames.get(replayer.next).cloned() else { return };
+    replayer.next += 1;
+    if replayer.next == replayer.frames.len() {
+        info!("Replay finished");
+    }
+
+    fixed.override_frame_delta(frame.delta);
+    let first = PlayerInput {
+        pressed: frame.pressed,
+        aim: frame.mouse_pos,
+    };
+    let inputs = iter::once(first).chain(frame.other_players);
+    let mut players: Vec<_> = player.iter_mut().collect();
+    players.sort_by_key(|&(&number, ..)| number);
+    for ((_, mut actions, mut aim), input) in players.into_iter().zip(inputs) {
+        aim.follow_cursor(Vec2::from_array(input.aim), true);
+        for action in Action::variants() {
+            if input.pressed.contains(&action) {
+                actions.press(action);
+            } else {
+                actions.release(action);
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Seeds the [`GameRng`] from `--seed <n>`, the `GAME_SEED` environment variable, or randomly if
+/// neither is given.
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = seed_from_args()
+            .or_else(|| std::env::var("GAME_SEED").ok()?.parse().ok())
+            .unwrap_or_else(|| rand::thread_rng().gen());
+        info!("Using RNG seed {seed}");
+        app.insert_resource(GameRng::seeded(seed));
+    }
+}
+
+fn seed_from_args() -> Option<u64> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            return match args.next()?.parse() {
+                Ok(seed) => Some(seed),
+                Err(err) => {
+                    error!("Invalid seed: {err}");
+                    None
+                }
+            };
+        }
+    }
+    None
+}
+
+/// The source of all gameplay randomness, such as spread, crits and drops, so that a run can be
+/// reproduced from its seed. Purely cosmetic randomness like screen shake doesn't need this.
+#[derive(Resource, Deref, DerefMut)]
+pub struct GameRng(pub ChaCha8Rng);
+
+impl GameRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(ChaCha8Rng::seed_from_u64(seed))
+    }
+}
@@ -0,0 +1,242 @@
+use std::fs;
+use std::iter;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::boss::{spawn_boss, Boss};
+use crate::difficulty::DifficultyScaling;
+use crate::enemy::{spawn_enemy, spawn_ranged_enemy, Enemy, Ranged, WaveSpawner};
+use crate::fixed::Interpolated;
+use crate::health::Health;
+use crate::pickup::EnemyDrops;
+use crate::player::{Player, PlayerNumber};
+use crate::rng::GameRng;
+use crate::score::Score;
+use crate::state::{GameState, GameplayEntity};
+use crate::weapon::{Loadout, Magazine};
+
+/// Saves the current run with F5 and loads it back with F9.
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(save_game)
+                .with_system(apply_save),
+        )
+        .add_system(load_game);
+    }
+}
+
+const SAVE_PATH: &str = "save.ron";
+/// Bumped whenever the format of [`SaveState`] changes, saves of other versions aren't loaded.
+const SAVE_VERSION: u32 = 1;
+
+/// Just enough of a save to tell whether the rest of it can be read.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SavedPlayer {
+    /// Which player this is, missing from saves made before there could be two players.
+    #[serde(default)]
+    number: usize,
+    pos: Vec2,
+    health: Health,
+    weapon: usize,
+    /// Rounds left in the magazine of every weapon in the loadout.
+    rounds: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SavedEnemy {
+    pos: Vec2,
+    health: Health,
+    /// Missing from saves made before there were ranged enemies.
+    #[serde(default)]
+    ranged: bool,
+    /// Missing from saves made before there were bosses.
+    #[serde(default)]
+    boss: bool,
+}
+
+/// Everything needed to pick a run back up where it was saved. Bullets and pickups are left out.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone)]
+struct SaveState {
+    version: u32,
+    /// The lowest numbered player alive when the game was saved.
+    player: SavedPlayer,
+    /// Any other players alive, missing from saves made before there could be two players.
+    #[serde(default)]
+    other_players: Vec<SavedPlayer>,
+    enemies: Vec<SavedEnemy>,
+    score: u32,
+    wave: usize,
+}
+
+impl SaveState {
+    fn read(path: &str) -> Result<Self, String> {
+        let ron = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let header: SaveHeader = ron::from_str(&ron).map_err(|err| err.to_string())?;
+        if header.version != SAVE_VERSION {
+            return Err(format!(
+                "it's from version {}, this game reads version {SAVE_VERSION}",
+                header.version
+            ));
+        }
+        ron::from_str(&ron).map_err(|err| err.to_string())
+    }
+
+    fn write(&self, path: &str) -> Result<(), String> {
+        let ron = ron::ser::to_string_pretty(self, default()).map_err(|err| err.to_string())?;
+        fs::write(path, ron).map_err(|err| err.to_string())
+    }
+}
+
+fn save_game(
+    keys: Res<Input<KeyCode>>,
+    player: Query<(&PlayerNumber, &Transform, &Health, &Loadout, &Magazine), With<Player>>,
+    enemies: Query<(&Transform, &Health, Option<&Ranged>, Option<&Boss>), With<Enemy>>,
+    score: Res<Score>,
+    spawner: Res<WaveSpawner>,
+) {
+    if !keys.just_pressed(KeyCode::F5) {
+        return;
+    }
+    let mut players: Vec<SavedPlayer> = player
+        .iter()
+        .map(|(number, tf, &health, loadout, magazine)| SavedPlayer {
+            number: **number,
+            pos: tf.translation.xy(),
+            health,
+            weapon: loadout.current,
+            rounds: loadout.rounds(magazine),
+        })
+        .collect();
+    if players.is_empty() {
+        return;
+    }
+    players.sort_by_key(|saved| saved.number);
+    let first = players.remove(0);
+
+    let save = SaveState {
+        version: SAVE_VERSION,
+        player: first,
+        other_players: players,
+        enemies: enemies
+            .iter()
+            .map(|(tf, &health, ranged, boss)| SavedEnemy {
+                pos: tf.translation.xy(),
+                health,
+                ranged: ranged.is_some(),
+                boss: boss.is_some(),
+            })
+            .collect(),
+        score: **score,
+        wave: spawner.wave,
+    };
+    match save.write(SAVE_PATH) {
+        Ok(()) => info!("Saved the game to {SAVE_PATH}"),
+        Err(err) => error!("Couldn't save the game to {SAVE_PATH}: {err}"),
+    }
+}
+
+/// Starts a new run to restore the save into, which [`apply_save`] does once the arena is set up
+/// again.
+fn load_game(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    entities: Query<Entity, With<GameplayEntity>>,
+) {
+    if !keys.just_pressed(KeyCode::F9) {
+        return;
+    }
+    let save = match SaveState::read(SAVE_PATH) {
+        Ok(save) => save,
+        Err(err) => {
+            error!("Couldn't load the game from {SAVE_PATH}: {err}");
+            return;
+        }
+    };
+
+    let restarted = match state.current() {
+        GameState::Playing => {
+            for entity in &entities {
+                commands.entity(entity).despawn_recursive();
+            }
+            state.restart()
+        }
+        // Leaving the game over screen clears the old run already
+        GameState::GameOver => state.set(GameState::Playing),
+        GameState::Paused => return,
+    };
+    if restarted.is_ok() {
+        commands.insert_resource(save);
+    }
+}
+
+fn apply_save(
+    mut commands: Commands,
+    save: Option<Res<SaveState>>,
+    mut player: Query<
+        (
+            Entity,
+            &PlayerNumber,
+            &mut Transform,
+            &mut Health,
+            &mut Loadout,
+            &mut Magazine,
+        ),
+        With<Player>,
+    >,
+    drops: Res<EnemyDrops>,
+    difficulty: Res<DifficultyScaling>,
+    mut score: ResMut<Score>,
+    mut spawner: ResMut<WaveSpawner>,
+    mut rng: ResMut<GameRng>,
+) {
+    let Some(save) = save else { return };
+    // Wait for the new run's players to be spawned
+    if player.is_empty() {
+        return;
+    }
+
+    let saved: Vec<&SavedPlayer> = iter::once(&save.player)
+        .chain(&save.other_players)
+        .collect();
+    for (entity, number, mut tf, mut health, mut loadout, mut magazine) in &mut player {
+        let Some(saved) = saved.iter().find(|saved| saved.number == **number) else {
+            // Players who were dead when the game was saved die again right away
+            health.current = 0.0;
+            continue;
+        };
+        let pos = saved.pos.extend(tf.translation.z);
+        tf.translation = pos;
+        commands.entity(entity).insert(Interpolated::new(pos));
+        *health = saved.health;
+        loadout.restore(saved.weapon, &saved.rounds, &mut magazine);
+    }
+
+    for enemy in &save.enemies {
+        let (pos, drops) = (enemy.pos, drops.0.clone());
+        let entity = if enemy.boss {
+            spawn_boss(&mut commands, pos, drops, &difficulty)
+        } else if enemy.ranged {
+            spawn_ranged_enemy(&mut commands, pos, drops, &difficulty, &mut **rng)
+        } else {
+            spawn_enemy(&mut commands, pos, drops, &difficulty, &mut **rng)
+        };
+        commands.entity(entity).insert(enemy.health);
+    }
+    **score = save.score;
+    spawner.wave = save.wave;
+
+    commands.remove_resource::<SaveState>();
+    info!("Loaded the game from {SAVE_PATH}");
+}
@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+
+use crate::enemy::EnemyKilled;
+use crate::game_time::GameTime;
+use crate::tuning::Tuning;
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Score>()
+            .init_resource::<Combo>()
+            .add_event::<ComboMilestone>()
+            .add_system(score_kills)
+            .add_system(end_combo.after(score_kills));
+    }
+}
+
+/// A [`ComboMilestone`] is sent every time the combo reaches a multiple of this.
+const COMBO_MILESTONE: u32 = 10;
+
+#[derive(Resource, Default, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct Score(pub u32);
+
+/// Kills in quick succession, each one within the combo window of the last. The longer the
+/// combo, the more points kills are worth.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct Combo {
+    pub count: u32,
+    /// Time left for the next kill to keep the combo going. Only runs during a combo, it's
+    /// restarted with the tuned combo window on every kill.
+    pub timer: Timer,
+}
+
+impl Combo {
+    /// What points are multiplied by at the current count, going up by `combo_bonus` with every
+    /// kill after the first until `max_combo_multiplier`.
+    pub fn multiplier(&self, tuning: &Tuning) -> f32 {
+        let bonus = tuning.combo_bonus * self.count.saturating_sub(1) as f32;
+        (1.0 + bonus).min(tuning.max_combo_multiplier)
+    }
+}
+
+/// Sent when a combo reaches a milestone, e.g. to celebrate it with sounds or effects.
+pub struct ComboMilestone {
+    pub count: u32,
+}
+
+fn score_kills(
+    mut score: ResMut<Score>,
+    mut combo: ResMut<Combo>,
+    mut killed: EventReader<EnemyKilled>,
+    mut milestones: EventWriter<ComboMilestone>,
+    tuning: Res<Tuning>,
+) {
+    for kill in killed.iter() {
+        combo.count += 1;
+        combo.timer = Timer::from_seconds(tuning.combo_window, TimerMode::Once);
+        **score += (kill.points as f32 * combo.multiplier(&tuning)).round() as u32;
+        if combo.count % COMBO_MILESTONE == 0 {
+            milestones.send(ComboMilestone { count: combo.count });
+        }
+    }
+}
+
+/// Drops the combo once the window for the next kill has passed.
+fn end_combo(mut combo: ResMut<Combo>, time: Res<GameTime>) {
+    if combo.count == 0 {
+        return;
+    }
+    if combo.timer.tick(time.delta()).finished() {
+        combo.count = 0;
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, PresentMode, WindowMode};
+use serde::{Deserialize, Serialize};
+
+/// Where the settings are read from, missing settings keep their defaults.
+pub const SETTINGS_PATH: &str = "settings.ron";
+
+const MIN_SIZE: Vec2 = Vec2::new(640.0, 480.0);
+const MAX_SIZE: Vec2 = Vec2::new(7680.0, 4320.0);
+/// How long before the end of a capped frame the frame rate limiter stops sleeping and spins
+/// instead, as sleeping can overshoot by about this much.
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// Loads the settings file. The window has to be configured before the app is built, so load
+/// this first and then add it as a plugin to make the settings available as resources.
+pub struct SettingsPlugin {
+    window: WindowSettings,
+    quality: GraphicsQuality,
+    /// Problems found while loading, logged once logging is set up.
+    problems: Vec<String>,
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        for problem in &self.problems {
+            warn!("{problem}");
+        }
+        app.insert_resource(self.window.clone())
+            .insert_resource(self.quality)
+            .add_system(toggle_fullscreen)
+            .add_system(toggle_cursor_confinement)
+            .add_system(toggle_shake_rotation)
+            .add_system_to_stage(CoreStage::Last, limit_frame_rate);
+    }
+}
+
+impl SettingsPlugin {
+    pub fn load() -> Self {
+        let mut problems = Vec::new();
+        let settings = match fs::read_to_string(SETTINGS_PATH) {
+            Ok(ron) => match ron::from_str::<Settings>(&ron) {
+                Ok(settings) => settings,
+                Err(err) => {
+                    problems.push(format!("Couldn't parse {SETTINGS_PATH}: {err}"));
+                    Settings::default()
+                }
+            },
+            // No settings file just means the defaults are used
+            Err(_) => Settings::default(),
+        };
+        let window = settings.window.validated(&mut problems);
+        Self {
+            window,
+            quality: settings.quality,
+            problems,
+        }
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        self.window.descriptor()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct Settings {
+    window: WindowSettings,
+    quality: GraphicsQuality,
+}
+
+impl Settings {
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|ron| fs::write(SETTINGS_PATH, ron).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            error!("Couldn't save settings: {err}");
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub width: f32,
+    pub height: f32,
+    pub resizable: bool,
+    pub vsync: bool,
+    /// Presents frames this way instead of picking a mode with or without vsync automatically.
+    pub present_mode: Option<PresentModeSetting>,
+    /// Most frames per second to render, for machines that would render far more than needed
+    /// and waste power doing it. Zero for no cap.
+    pub fps_cap: u32,
+    pub fullscreen: bool,
+    /// Keeps the cursor inside the window, so aiming near its edges doesn't leave it.
+    pub confine_cursor: bool,
+    /// Whether screen shake rotates the view as well as moving it, turning this off helps with
+    /// motion sickness.
+    pub shake_rotation: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 1200.0,
+            height: 800.0,
+            resizable: false,
+            vsync: true,
+            present_mode: None,
+            fps_cap: 0,
+            fullscreen: false,
+            confine_cursor: false,
+            shake_rotation: true,
+        }
+    }
+}
+
+/// A present mode that isn't picked automatically, see [`PresentMode`] for what each does. Not
+/// every platform supports every mode, unsupported ones fall back to `Fifo`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentModeSetting {
+    /// No vsync, frames are shown right away and may tear.
+    Immediate,
+    /// Vsync, waits for the display so it never tears.
+    Fifo,
+    /// Vsync without waiting, the newest frame is shown at the next refresh.
+    Mailbox,
+}
+
+/// How detailed effects are, lower is lighter on slow machines. Only read at startup.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphicsQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl GraphicsQuality {
+    /// Multiplier for how many particles effects hold and spawn.
+    fn particle_scale(self) -> f32 {
+        match self {
+            Self::Low => 0.25,
+            Self::Medium => 0.5,
+            Self::High => 1.0,
+        }
+    }
+
+    /// A number of particles to spawn, or a spawn rate, scaled down for this quality.
+    pub fn particles(self, count: f32) -> f32 {
+        (count * self.particle_scale()).round().max(1.0)
+    }
+
+    /// The capacity of an effect, scaled down for this quality.
+    pub fn capacity(self, capacity: u32) -> u32 {
+        self.particles(capacity as f32) as u32
+    }
+}
+
+impl WindowSettings {
+    /// Clamps the size to something sensible, noting down what had to be changed.
+    fn validated(mut self, problems: &mut Vec<String>) -> Self {
+        let default = Self::default();
+        let size = Vec2::new(self.width, self.height);
+        let valid = if size.is_finite() {
+            size.clamp(MIN_SIZE, MAX_SIZE)
+        } else {
+            Vec2::new(default.width, default.height)
+        };
+        if valid != size {
+            problems.push(format!(
+                "Window size {}x{} is out of range, using {}x{} instead",
+                size.x, size.y, valid.x, valid.y
+            ));
+        }
+        self.width = valid.x;
+        self.height = valid.y;
+        self
+    }
+
+    pub fn mode(&self) -> WindowMode {
+        if self.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+
+    /// Confined rather than locked, as a locked cursor stops moving and `cursor_position` would
+    /// no longer follow the mouse.
+    pub fn cursor_grab_mode(&self) -> CursorGrabMode {
+        if self.confine_cursor {
+            CursorGrabMode::Confined
+        } else {
+            CursorGrabMode::None
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        match self.present_mode {
+            Some(PresentModeSetting::Immediate) => PresentMode::Immediate,
+            Some(PresentModeSetting::Fifo) => PresentMode::Fifo,
+            Some(PresentModeSetting::Mailbox) => PresentMode::Mailbox,
+            None if self.vsync => PresentMode::AutoVsync,
+            None => PresentMode::AutoNoVsync,
+        }
+    }
+
+    /// Writes these settings to the settings file so they're used again next launch.
+    pub fn save(&self, quality: GraphicsQuality) {
+        Settings {
+            window: self.clone(),
+            quality,
+        }
+        .save();
+    }
+
+    fn descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            width: self.width,
+            height: self.height,
+            position: WindowPosition::Centered,
+            title: "Rust game!".into(),
+            resizable: self.resizable,
+            present_mode: self.present_mode(),
+            mode: self.mode(),
+            cursor_grab_mode: self.cursor_grab_mode(),
+            ..default()
+        }
+    }
+}
+
+/// Switches between windowed and borderless fullscreen with F11, remembering the choice.
+fn toggle_fullscreen(
+    mut settings: ResMut<WindowSettings>,
+    quality: Res<GraphicsQuality>,
+    mut windows: ResMut<Windows>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::F11) {
+        return;
+    }
+    settings.fullscreen = !settings.fullscreen;
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_mode(settings.mode());
+    }
+    settings.save(*quality);
+}
+
+/// Toggles keeping the cursor inside the window with F10, remembering the choice.
+fn toggle_cursor_confinement(
+    mut settings: ResMut<WindowSettings>,
+    quality: Res<GraphicsQuality>,
+    mut windows: ResMut<Windows>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::F10) {
+        return;
+    }
+    settings.confine_cursor = !settings.confine_cursor;
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_cursor_grab_mode(settings.cursor_grab_mode());
+    }
+    settings.save(*quality);
+}
+
+/// Toggles whether screen shake rotates the view with F8, remembering the choice.
+fn toggle_shake_rotation(
+    mut settings: ResMut<WindowSettings>,
+    quality: Res<GraphicsQuality>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::F8) {
+        return;
+    }
+    settings.shake_rotation = !settings.shake_rotation;
+    settings.save(*quality);
+}
+
+/// Holds each frame back until `fps_cap` allows the next one, sleeping for most of the wait and
+/// spinning for the rest, so it ends on time.
+fn limit_frame_rate(settings: Res<WindowSettings>, mut last_frame: Local<Option<Instant>>) {
+    if let Some(last) = last_frame.filter(|_| settings.fps_cap > 0) {
+        let end = last + Duration::from_secs_f64(1.0 / settings.fps_cap as f64);
+        if let Some(sleep) = end
+            .checked_duration_since(Instant::now())
+            .and_then(|left| left.checked_sub(SPIN_MARGIN))
+        {
+            std::thread::sleep(sleep);
+        }
+        while Instant::now() < end {
+            std::hint::spin_loop();
+        }
+    }
+    *last_frame = Some(Instant::now());
+}
@@ -0,0 +1,253 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::enemy::Enemy;
+use crate::fixed::{FixedUpdateStage, Simulation};
+
+/// Keeps an [`EnemyGrid`] of where every enemy is, rebuilt before each fixed step moves them.
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EnemyGrid>()
+            .add_system_to_stage(FixedUpdateStage, rebuild_enemy_grid.before(Simulation));
+    }
+}
+
+/// Side length of a grid cell, about as far as enemies look for each other.
+const CELL_SIZE: f32 = 100.0;
+
+/// Enemies bucketed into a uniform grid by position, so finding the ones near a point only
+/// looks at the few cells around it rather than at every enemy.
+#[derive(Resource, Debug, Clone)]
+pub struct EnemyGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<(Entity, Vec2)>>,
+    positions: HashMap<Entity, Vec2>,
+    /// The lowest and highest occupied cell on each axis, none when the grid is empty.
+    extent: Option<(IVec2, IVec2)>,
+}
+
+impl Default for EnemyGrid {
+    fn default() -> Self {
+        Self::new(CELL_SIZE)
+    }
+}
+
+impl EnemyGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            positions: HashMap::default(),
+            extent: None,
+        }
+    }
+
+    fn cell(&self, pos: Vec2) -> IVec2 {
+        (pos / self.cell_size).floor().as_ivec2()
+    }
+
+    /// Empties the grid, keeping the memory of its cells around for the next rebuild.
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.positions.clear();
+        self.extent = None;
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec2) {
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_default().push((entity, pos));
+        self.positions.insert(entity, pos);
+        self.extent = Some(match self.extent {
+            Some((min, max)) => (min.min(cell), max.max(cell)),
+            None => (cell, cell),
+        });
+    }
+
+    /// Where `entity` was when the grid was built, if it's in there.
+    pub fn position(&self, entity: Entity) -> Option<Vec2> {
+        self.positions.get(&entity).copied()
+    }
+
+    /// Every entity within `radius` of `pos`, with its position.
+    pub fn query_nearby(
+        &self,
+        pos: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let min = self.cell(pos - radius);
+        let max = self.cell(pos + radius);
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(move |(_, other)| other.distance_squared(pos) <= radius * radius)
+    }
+
+    /// The entity closest to `pos`, searching rings of cells further and further out until
+    /// nothing further out could be closer.
+    pub fn nearest(&self, pos: Vec2) -> Option<(Entity, Vec2)> {
+        let (min, max) = self.extent?;
+        let center = self.cell(pos);
+        // Past this ring every occupied cell has been looked at
+        let last_ring = (center - min).abs().max((max - center).abs()).max_element();
+
+        let mut best: Option<(Entity, Vec2, f32)> = None;
+        for ring in 0..=last_ring {
+            // Anything in this ring or beyond is at least this far away
+            let ring_distance = (ring - 1).max(0) as f32 * self.cell_size;
+            if best.map_or(false, |(.., distance)| distance <= ring_distance) {
+                break;
+            }
+            for cell in ring_cells(center, ring) {
+                for &(entity, other) in self.cells.get(&cell).into_iter().flatten() {
+                    let distance = other.distance(pos);
+                    if best.map_or(true, |(.., best)| distance < best) {
+                        best = Some((entity, other, distance));
+                    }
+                }
+            }
+        }
+        best.map(|(entity, other, _)| (entity, other))
+    }
+}
+
+/// The cells exactly `ring` cells away from `center` along either axis.
+fn ring_cells(center: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+    (-ring..=ring).flat_map(move |y| {
+        // Only the left and right edge of the rows between the top and bottom one
+        let step = if y.abs() == ring {
+            1
+        } else {
+            (2 * ring).max(1) as usize
+        };
+        (-ring..=ring)
+            .step_by(step)
+            .map(move |x| center + IVec2::new(x, y))
+    })
+}
+
+fn rebuild_enemy_grid(
+    mut grid: ResMut<EnemyGrid>,
+    enemies: Query<(Entity, &Transform), With<Enemy>>,
+) {
+    grid.clear();
+    for (entity, tf) in &enemies {
+        grid.insert(entity, tf.translation.xy());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    /// A grid of `count` entities scattered over an arena a few thousand units across, along
+    /// with the same entities in a plain list.
+    fn scattered(count: usize) -> (EnemyGrid, Vec<(Entity, Vec2)>) {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut grid = EnemyGrid::default();
+        let mut list = Vec::new();
+        for i in 0..count {
+            let entity = Entity::from_raw(i as u32);
+            let pos = Vec2::new(
+                rng.gen_range(-2000.0..2000.0),
+                rng.gen_range(-2000.0..2000.0),
+            );
+            grid.insert(entity, pos);
+            list.push((entity, pos));
+        }
+        (grid, list)
+    }
+
+    fn nearest_by_scan(list: &[(Entity, Vec2)], pos: Vec2) -> Option<(Entity, Vec2)> {
+        list.iter()
+            .copied()
+            .min_by(|(_, a), (_, b)| a.distance_squared(pos).total_cmp(&b.distance_squared(pos)))
+    }
+
+    #[test]
+    fn finds_the_same_neighbors_as_a_scan() {
+        let (grid, list) = scattered(300);
+        for pos in [
+            Vec2::ZERO,
+            Vec2::new(1234.5, -876.0),
+            Vec2::new(-1999.0, 1999.0),
+        ] {
+            let mut nearby: Vec<_> = grid.query_nearby(pos, 250.0).map(|(e, _)| e).collect();
+            let mut scanned: Vec<_> = list
+                .iter()
+                .filter(|(_, other)| other.distance(pos) <= 250.0)
+                .map(|&(e, _)| e)
+                .collect();
+            nearby.sort();
+            scanned.sort();
+            assert_eq!(nearby, scanned);
+        }
+    }
+
+    #[test]
+    fn finds_the_same_nearest_as_a_scan() {
+        let (grid, list) = scattered(300);
+        // Including points well outside of where the entities are
+        for pos in [
+            Vec2::ZERO,
+            Vec2::new(640.0, 10.0),
+            Vec2::new(-5000.0, 3000.0),
+        ] {
+            assert_eq!(grid.nearest(pos), nearest_by_scan(&list, pos));
+        }
+    }
+
+    #[test]
+    fn empty_grid_has_nothing_nearby() {
+        let mut grid = EnemyGrid::default();
+        grid.insert(Entity::from_raw(0), Vec2::ZERO);
+        grid.clear();
+        assert_eq!(grid.nearest(Vec2::ZERO), None);
+        assert_eq!(grid.query_nearby(Vec2::ZERO, 100.0).count(), 0);
+        assert_eq!(grid.position(Entity::from_raw(0)), None);
+    }
+
+    /// Compares the grid against scanning every enemy, run with
+    /// `cargo test --release bench_nearest -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_nearest() {
+        const QUERIES: usize = 10_000;
+        for count in [100, 300, 500] {
+            let (grid, list) = scattered(count);
+            let mut rng = ChaCha8Rng::seed_from_u64(1);
+            let points: Vec<_> = (0..QUERIES)
+                .map(|_| {
+                    Vec2::new(
+                        rng.gen_range(-2000.0..2000.0),
+                        rng.gen_range(-2000.0..2000.0),
+                    )
+                })
+                .collect();
+
+            let start = Instant::now();
+            let scanned: Vec<_> = points
+                .iter()
+                .map(|&pos| nearest_by_scan(&list, pos))
+                .collect();
+            let scan_time = start.elapsed();
+            let start = Instant::now();
+            let found: Vec<_> = points.iter().map(|&pos| grid.nearest(pos)).collect();
+            let grid_time = start.elapsed();
+
+            assert_eq!(found, scanned);
+            println!("{count} enemies, {QUERIES} queries: scan {scan_time:?}, grid {grid_time:?}");
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::health::Health;
+use crate::hud::{hud_text_style, HudFont};
+use crate::player::{Player, PlayerDied};
+use crate::score::{Combo, Score};
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(GameState::Playing)
+            .add_system(toggle_pause)
+            .add_system(game_over_on_death)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused)
+                    .with_system(spawn_pause_menu)
+                    .with_system(pause_physics)
+                    .with_system(pause_effects),
+            )
+            .add_system_set(SystemSet::on_update(GameState::Paused).with_system(pause_menu_buttons))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused)
+                    .with_system(despawn_pause_menu)
+                    .with_system(resume_physics)
+                    .with_system(resume_effects),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(spawn_game_over))
+            .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(restart))
+            .add_system_set(
+                SystemSet::on_exit(GameState::GameOver)
+                    .with_system(despawn_game_over)
+                    .with_system(despawn_gameplay_entities),
+            );
+    }
+}
+
+/// `Paused` is pushed on top of `Playing`, so `on_enter(GameState::Playing)` only runs when a
+/// new game starts and can be used to spawn the arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/// Marks entities belonging to the current run, which are all despawned on restart.
+#[derive(Component)]
+pub struct GameplayEntity;
+
+#[derive(Component)]
+struct GameOverScreen;
+
+#[derive(Component)]
+struct PauseMenu;
+
+/// A particle effect that was spawning particles until the game was paused.
+#[derive(Component)]
+struct PausedEffect;
+
+#[derive(Component, Clone, Copy)]
+enum PauseButton {
+    Resume,
+    Quit,
+}
+
+fn toggle_pause(mut state: ResMut<State<GameState>>, mut keys: ResMut<Input<KeyCode>>) {
+    if !keys.just_pressed(KeyCode::Escape) {
+        return;
+    }
+
+    let _ = match state.current() {
+        GameState::Playing => state.push(GameState::Paused),
+        GameState::Paused => state.pop(),
+        GameState::GameOver => return,
+    };
+    // Don't let the same press leak into other systems on the same frame
+    keys.clear_just_pressed(KeyCode::Escape);
+}
+
+fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+/// Stops every particle effect from spawning particles, so trails and debris don't keep
+/// streaming out of bullets standing still.
+///
+/// Particles already spawned are *not* frozen: Hanabi simulates them on the real [`Time`], with no
+/// way to stop its clock, so they keep flying and fade out under the pause menu. The only way to
+/// stop it simulating an effect is hiding it, which would hide the bullets carrying the trails
+/// too. They're all short lived, so at worst a resumed game has a few fewer sparks around.
+fn pause_effects(mut commands: Commands, mut effects: Query<(Entity, &mut ParticleEffect)>) {
+    for (entity, mut effect) in &mut effects {
+        let Some(spawner) = effect.maybe_spawner() else { continue };
+        if spawner.is_active() {
+            spawner.set_active(false);
+            commands.entity(entity).insert(PausedEffect);
+        }
+    }
+}
+
+fn resume_effects(
+    mut commands: Commands,
+    mut effects: Query<(Entity, &mut ParticleEffect), With<PausedEffect>>,
+) {
+    for (entity, mut effect) in &mut effects {
+        if let Some(spawner) = effect.maybe_spawner() {
+            spawner.set_active(true);
+        }
+        commands.entity(entity).remove::<PausedEffect>();
+    }
+}
+
+fn spawn_pause_menu(mut commands: Commands, font: Res<HudFont>) {
+    commands
+        .spawn((
+            Name::new("Pause menu"),
+            PauseMenu,
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Paused", hud_text_style(&font)));
+            for (button, label) in [(PauseButton::Resume, "Resume"), (PauseButton::Quit, "Quit")] {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(160.0), Val::Px(40.0)),
+                                margin: UiRect::all(Val::Px(8.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: Color::DARK_GRAY.into(),
+                            ..default()
+                        },
+                        button,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label, hud_text_style(&font)));
+                    });
+            }
+        });
+}
+
+fn pause_menu_buttons(
+    buttons: Query<(&Interaction, &PauseButton), Changed<Interaction>>,
+    mut state: ResMut<State<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            PauseButton::Resume => {
+                let _ = state.pop();
+            }
+            PauseButton::Quit => exit.send(AppExit),
+        }
+    }
+}
+
+fn despawn_pause_menu(mut commands: Commands, menu: Query<Entity, With<PauseMenu>>) {
+    for entity in &menu {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Ends the run once every player has died.
+fn game_over_on_death(
+    mut state: ResMut<State<GameState>>,
+    mut died: EventReader<PlayerDied>,
+    players: Query<&Health, With<Player>>,
+) {
+    // Players who died this frame haven't been despawned yet
+    let all_dead = players.iter().all(Health::is_dead);
+    if died.iter().count() > 0 && all_dead && *state.current() == GameState::Playing {
+        let _ = state.set(GameState::GameOver);
+    }
+}
+
+fn spawn_game_over(mut commands: Commands, font: Res<HudFont>, score: Res<Score>) {
+    commands
+        .spawn((
+            Name::new("Game over screen"),
+            GameOverScreen,
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for line in [
+                "Game over".to_string(),
+                format!("Final score: {}", score.0),
+                "Press Enter to restart".to_string(),
+            ] {
+                parent.spawn(TextBundle::from_section(line, hud_text_style(&font)));
+            }
+        });
+}
+
+fn restart(mut state: ResMut<State<GameState>>, keys: Res<Input<KeyCode>>) {
+    if keys.just_pressed(KeyCode::Return) {
+        let _ = state.set(GameState::Playing);
+    }
+}
+
+fn despawn_game_over(mut commands: Commands, screen: Query<Entity, With<GameOverScreen>>) {
+    for entity in &screen {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn despawn_gameplay_entities(
+    mut commands: Commands,
+    entities: Query<Entity, With<GameplayEntity>>,
+    mut score: ResMut<Score>,
+    mut combo: ResMut<Combo>,
+) {
+    for entity in &entities {
+        commands.entity(entity).despawn_recursive();
+    }
+    *score = Score::default();
+    *combo = Combo::default();
+}
@@ -0,0 +1,131 @@
+use std::f32::consts::PI;
+
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+use crate::bullet::{fire_bullets, Bullet, BulletStats, FireBullet, Shooter};
+use crate::game_time::GameTime;
+use crate::health::Faction;
+use crate::player::{Player, PlayerNumber, PLAYER_RADIUS};
+use crate::state::GameState;
+use crate::tuning::Tuning;
+
+/// Sprays bullets in every direction from player one with `--stress-test`, to see how the game
+/// holds up with lots of bullets and trails around. `+` and `-` change how many are fired, and the
+/// frame time is logged every second.
+pub struct StressTestPlugin;
+
+impl Plugin for StressTestPlugin {
+    fn build(&self, app: &mut App) {
+        if !std::env::args().skip(1).any(|arg| arg == "--stress-test") {
+            return;
+        }
+        app.init_resource::<StressTest>()
+            .add_system(adjust_stress_rate)
+            .add_system(report_stress)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(fire_stress_bullets.before(fire_bullets)),
+            );
+    }
+}
+
+/// Bullets per second fired when the stress test starts.
+const START_RATE: f32 = 200.0;
+/// How much `+` and `-` change the bullets fired per second by.
+const RATE_STEP: f32 = 100.0;
+/// Seconds between two reports of how the game is holding up.
+const REPORT_INTERVAL: f32 = 1.0;
+/// Angle between two bullets fired one after the other, the golden angle spreads them evenly
+/// in every direction however many are fired.
+const GOLDEN_ANGLE: f32 = PI * 0.763_932;
+
+#[derive(Resource)]
+struct StressTest {
+    /// Bullets fired per second.
+    rate: f32,
+    /// Bullets that are due but not fired yet, as fewer than one bullet may be due each frame.
+    pending: f32,
+    /// The way the next bullet is fired.
+    angle: f32,
+}
+
+impl Default for StressTest {
+    fn default() -> Self {
+        Self {
+            rate: START_RATE,
+            pending: 0.0,
+            angle: 0.0,
+        }
+    }
+}
+
+fn adjust_stress_rate(mut stress: ResMut<StressTest>, keys: Res<Input<KeyCode>>) {
+    let step = if keys.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        RATE_STEP
+    } else if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        -RATE_STEP
+    } else {
+        return;
+    };
+    stress.rate = (stress.rate + step).max(0.0);
+    info!("Stress test firing {} bullets per second", stress.rate);
+}
+
+/// Fires the bullets due this frame from the player, on the player's side so they don't hurt
+/// them.
+fn fire_stress_bullets(
+    mut stress: ResMut<StressTest>,
+    player: Query<(Entity, &Transform, &PlayerNumber), With<Player>>,
+    mut fire: EventWriter<FireBullet>,
+    time: Res<GameTime>,
+    tuning: Res<Tuning>,
+) {
+    // The lowest numbered player left, so the stress test carries on if player one dies
+    let Some((entity, tf, _)) = player.iter().min_by_key(|&(_, _, &number)| number) else {
+        return;
+    };
+    stress.pending += stress.rate * time.delta_seconds();
+    while stress.pending >= 1.0 {
+        stress.pending -= 1.0;
+        stress.angle = (stress.angle + GOLDEN_ANGLE) % (2.0 * PI);
+        fire.send(FireBullet {
+            origin: tf.translation,
+            dir: Vec2::from_angle(stress.angle),
+            stats: BulletStats::tuned(&tuning),
+            shooter: Shooter {
+                entity,
+                faction: Faction::Player,
+                velocity: Vec2::ZERO,
+                pos: tf.translation.xy(),
+                radius: PLAYER_RADIUS,
+            },
+        });
+    }
+}
+
+fn report_stress(
+    stress: Res<StressTest>,
+    bullets: Query<(), With<Bullet>>,
+    diagnostics: Res<Diagnostics>,
+    time: Res<Time>,
+    mut since_report: Local<f32>,
+) {
+    *since_report += time.delta_seconds();
+    if *since_report < REPORT_INTERVAL {
+        return;
+    }
+    *since_report = 0.0;
+
+    let frame_time = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|diagnostic| diagnostic.smoothed())
+        .unwrap_or(0.0);
+    info!(
+        "Stress test: {} bullets per second, {} bullets alive, {:.2} ms per frame",
+        stress.rate,
+        bullets.iter().count(),
+        1000.0 * frame_time,
+    );
+}
@@ -0,0 +1,150 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::bullet::ricochet;
+use crate::collision::bullet_groups;
+use crate::player::{aim_upper_body, find_muzzle, muzzle, Action, AimTarget, Muzzle, Player};
+use crate::state::GameState;
+use crate::wall::PassableFrom;
+
+pub struct TrajectoryPreviewPlugin;
+
+impl Plugin for TrajectoryPreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_trajectory_dots)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(update_trajectory_preview.after(aim_upper_body)),
+            );
+    }
+}
+
+/// Total length of the previewed path, including the part after the ricochet.
+const PREVIEW_LENGTH: f32 = 600.0;
+const DOT_SPACING: f32 = 20.0;
+/// How far off a wall the ricocheted ray starts, so it doesn't hit the same wall again.
+const RICOCHET_OFFSET: f32 = 0.1;
+
+#[derive(Component)]
+struct TrajectoryDot;
+
+/// Spawns enough dots up front to preview a single path.
+fn spawn_trajectory_dots(mut commands: Commands) {
+    let dots = (PREVIEW_LENGTH / DOT_SPACING) as usize;
+    for _ in 0..dots {
+        spawn_dot(&mut commands, Vec2::ZERO, false);
+    }
+}
+
+fn spawn_dot(commands: &mut Commands, pos: Vec2, visible: bool) {
+    commands.spawn((
+        Name::new("Trajectory dot"),
+        TrajectoryDot,
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(1.0, 1.0, 1.0, 0.5),
+                custom_size: Some(Vec2::splat(3.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(pos.extend(0.5)),
+            visibility: Visibility {
+                is_visible: visible,
+            },
+            ..default()
+        },
+    ));
+}
+
+/// Follows the path a bullet fired now would take, bouncing once off the first wall it hits the
+/// way [`ricochet`] bounces bullets, and returns the straight segments making it up.
+fn predict_path(
+    rapier: &RapierContext,
+    filter: QueryFilter,
+    passable: &Query<&PassableFrom>,
+    pos: Vec2,
+    dir: Vec2,
+) -> [(Vec2, Vec2); 2] {
+    let blocks = |dir: Vec2| {
+        move |entity| {
+            passable
+                .get(entity)
+                .map_or(true, |passable| !passable.lets_through(dir))
+        }
+    };
+    let blocks_shot = blocks(dir);
+    let shot_filter = filter.predicate(&blocks_shot);
+    let Some((_, hit)) =
+        rapier.cast_ray_and_get_normal(pos, dir, PREVIEW_LENGTH, true, shot_filter)
+    else {
+        return [(pos, pos + PREVIEW_LENGTH * dir), (pos, pos)];
+    };
+
+    // A grazing hit stops the bullet rather than bouncing it
+    let Some((bounce_dir, away)) = ricochet(dir, hit.normal) else {
+        return [(pos, hit.point), (hit.point, hit.point)];
+    };
+    let remaining = PREVIEW_LENGTH - hit.toi;
+    let bounce_start = hit.point + RICOCHET_OFFSET * away;
+    let blocks_bounce = blocks(bounce_dir);
+    let bounce_filter = filter.predicate(&blocks_bounce);
+    let bounce_end = match rapier.cast_ray(bounce_start, bounce_dir, remaining, true, bounce_filter)
+    {
+        Some((_, toi)) => bounce_start + toi * bounce_dir,
+        None => bounce_start + remaining * bounce_dir,
+    };
+    [(pos, hit.point), (bounce_start, bounce_end)]
+}
+
+/// Previews the path of every player's next shot while they're aiming, spawning more dots when
+/// several players are aiming at once.
+fn update_trajectory_preview(
+    mut commands: Commands,
+    player: Query<(Entity, &ActionState<Action>, &AimTarget), With<Player>>,
+    children: Query<&Children>,
+    muzzles: Query<&GlobalTransform, With<Muzzle>>,
+    mut dots: Query<(&mut Transform, &mut Visibility), With<TrajectoryDot>>,
+    passable: Query<&PassableFrom>,
+    rapier: Res<RapierContext>,
+) {
+    let mut points = Vec::new();
+    for (entity, actions, aim) in &player {
+        if !actions.pressed(Action::Aim) {
+            continue;
+        }
+        let Some(muzzle_tf) = find_muzzle(entity, &children, &muzzles) else { continue };
+        let (pos, dir) = muzzle(muzzle_tf, aim.point);
+        let filter = QueryFilter::new()
+            .exclude_collider(entity)
+            .groups(bullet_groups());
+        let path = predict_path(&rapier, filter, &passable, pos.xy(), dir.normalize());
+
+        // Walk along the path placing a dot every `DOT_SPACING`, carrying the leftover distance
+        // over the ricochet so the spacing stays even
+        let mut offset = 0.0;
+        for (start, end) in path {
+            let length = start.distance(end);
+            let dir = (end - start).normalize_or_zero();
+            while offset < length {
+                points.push(start + offset * dir);
+                offset += DOT_SPACING;
+            }
+            offset -= length;
+        }
+    }
+
+    let mut points = points.into_iter();
+    for (mut dot_tf, mut visibility) in &mut dots {
+        match points.next() {
+            Some(point) => {
+                dot_tf.translation = point.extend(0.5);
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+    for point in points {
+        spawn_dot(&mut commands, point, true);
+    }
+}
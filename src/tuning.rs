@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::hot_reload::{load_ron_or, read_ron, FileWatcher};
+
+/// Loads the [`Tuning`] at startup and reloads it whenever its file changes, so the game can be
+/// balanced while it's running. Any [`TuningOverrides`] are applied on top of the file.
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_ron_or(TUNING_PATH, "default tuning", Tuning::default))
+            .init_resource::<TuningOverrides>()
+            .add_startup_system(apply_tuning_overrides)
+            .add_system(reload_tuning);
+    }
+}
+
+/// Where designers can tune the game, see [`Tuning`] for the format.
+const TUNING_PATH: &str = "assets/tuning.ron";
+/// Seconds between checks whether the tuning file has changed.
+const RELOAD_INTERVAL: f32 = 0.5;
+
+/// Numbers for balancing the game, e.g.
+///
+/// ```ron
+/// (
+///     walk_speed: 250.0,
+///     wave_interval: 6.0,
+/// )
+/// ```
+///
+/// Anything left out keeps its default.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tuning {
+    pub walk_speed: f32,
+    pub dash_speed: f32,
+    /// How far a dodge roll takes the player.
+    pub roll_distance: f32,
+    /// Seconds a dodge roll lasts, not counting the recovery after it.
+    pub roll_duration: f32,
+    /// Seconds from the start of a dodge roll that the player is invulnerable for.
+    pub roll_iframes: f32,
+    /// Speed of bullets fired by weapons that don't fly faster or slower than usual.
+    pub bullet_speed: f32,
+    /// Seconds bullets fly for unless their weapon gives them a different range.
+    pub bullet_lifetime: f32,
+    /// Whether bullets fired by a moving player carry the player's velocity along.
+    pub inherit_velocity: bool,
+    /// Speed of enemies chasing the player, they wander around slower than this.
+    pub enemy_speed: f32,
+    /// Enemies steer away from other enemies closer than this, so they don't clump together.
+    pub separation_radius: f32,
+    /// How strongly enemies head where they're going, relative to `separation_weight`.
+    pub chase_weight: f32,
+    /// How strongly enemies steer away from each other, relative to `chase_weight`.
+    pub separation_weight: f32,
+    /// Seconds between waves.
+    pub wave_interval: f32,
+    /// Number of enemies in the first wave.
+    pub wave_size: usize,
+    /// How many more enemies each subsequent wave brings.
+    pub wave_growth: usize,
+    /// Waves stop spawning enemies once this many are alive.
+    pub max_alive: usize,
+    /// Seconds after a kill in which the next one keeps the combo going.
+    pub combo_window: f32,
+    /// How much the score multiplier goes up with every kill in a combo.
+    pub combo_bonus: f32,
+    /// The most a combo can multiply the score of a kill by.
+    pub max_combo_multiplier: f32,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            walk_speed: 200.0,
+            dash_speed: 900.0,
+            roll_distance: 250.0,
+            roll_duration: 0.35,
+            roll_iframes: 0.25,
+            bullet_speed: 1500.0,
+            bullet_lifetime: 1.0,
+            inherit_velocity: false,
+            enemy_speed: 120.0,
+            separation_radius: 60.0,
+            chase_weight: 1.0,
+            separation_weight: 1.5,
+            wave_interval: 8.0,
+            wave_size: 3,
+            wave_growth: 2,
+            max_alive: 40,
+            combo_window: 3.0,
+            combo_bonus: 0.1,
+            max_combo_multiplier: 4.0,
+        }
+    }
+}
+
+/// Tuning set in code, e.g. through a plugin's builder, which takes precedence over the tuning
+/// file and stays in place when it's reloaded.
+#[derive(Resource, Default)]
+pub struct TuningOverrides(Vec<Box<dyn Fn(&mut Tuning) + Send + Sync>>);
+
+impl TuningOverrides {
+    fn apply(&self, tuning: &mut Tuning) {
+        for apply in &self.0 {
+            apply(tuning);
+        }
+    }
+}
+
+/// Has `apply` change the tuning whenever it's loaded, for plugins to call while they're built.
+pub fn override_tuning(app: &mut App, apply: impl Fn(&mut Tuning) + Send + Sync + 'static) {
+    app.world
+        .get_resource_or_insert_with(TuningOverrides::default)
+        .0
+        .push(Box::new(apply));
+}
+
+/// Applies the overrides to the tuning loaded at startup, once every plugin has added its own.
+fn apply_tuning_overrides(mut tuning: ResMut<Tuning>, overrides: Res<TuningOverrides>) {
+    overrides.apply(&mut tuning);
+}
+
+/// Polls the tuning file's modification time and reloads it when it changes. A file that can't
+/// be parsed leaves the current tuning in place.
+fn reload_tuning(
+    mut tuning: ResMut<Tuning>,
+    overrides: Res<TuningOverrides>,
+    time: Res<Time>,
+    mut watcher: Local<FileWatcher>,
+) {
+    if !watcher.due(time.delta_seconds(), RELOAD_INTERVAL) || !watcher.changed(TUNING_PATH) {
+        return;
+    }
+
+    match read_ron::<Tuning>(TUNING_PATH) {
+        Ok(mut reloaded) => {
+            info!("Reloaded {TUNING_PATH}");
+            overrides.apply(&mut reloaded);
+            if *tuning != reloaded {
+                *tuning = reloaded;
+            }
+        }
+        Err(err) => error!("Couldn't reload {TUNING_PATH}: {err}"),
+    }
+}
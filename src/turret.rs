@@ -0,0 +1,242 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+use crate::audio::{ListenerPos, SoundEffects};
+use crate::bullet::{fire_bullets, BulletStats, FireBullet, Shooter};
+use crate::collision::turret_groups;
+use crate::enemy::ENEMY_RADIUS;
+use crate::game_time::GameTime;
+use crate::health::{Faction, Health};
+use crate::player::{turn_towards, Action, AimTarget, Player};
+use crate::spatial::EnemyGrid;
+use crate::state::{GameState, GameplayEntity};
+use crate::tuning::Tuning;
+
+/// Lets the player put down turrets that shoot at the nearest enemy in range by themselves,
+/// until enemies wreck them.
+pub struct TurretPlugin;
+
+impl Plugin for TurretPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(place_turret)
+                .with_system(aim_turrets)
+                .with_system(turret_attack.after(aim_turrets).before(fire_bullets))
+                .with_system(turret_contact_damage)
+                .with_system(destroy_turrets),
+        );
+    }
+}
+
+const TURRET_RADIUS: f32 = 25.0;
+const TURRET_HEALTH: f32 = 150.0;
+/// Most turrets the player can have standing at once.
+const MAX_TURRETS: usize = 3;
+/// How far from the player turrets can be put down, towards where they're aiming.
+const PLACE_RANGE: f32 = 150.0;
+/// How far the barrel sticks out from the turret's center, bullets are fired from its tip.
+const BARREL_LENGTH: f32 = 35.0;
+/// Radians per second the barrel turns towards its target at.
+const BARREL_TURN_SPEED: f32 = 4.0;
+/// How far off its target the barrel can be pointing and still fire.
+const AIM_TOLERANCE: f32 = 0.1;
+/// Damage per second an enemy pressed against a turret deals to it.
+const CONTACT_DAMAGE: f32 = 20.0;
+
+/// Shoots at the nearest enemy within `range`, turning its barrel towards it first.
+#[derive(Component, Debug, Clone)]
+pub struct Turret {
+    pub range: f32,
+    /// Shots per second.
+    pub fire_rate: f32,
+    /// Damage dealt by each bullet.
+    pub damage: f32,
+    /// Time until the turret can fire again.
+    cooldown: f32,
+    /// Where the enemy the barrel is turning towards was when the enemy grid was built.
+    target: Option<Vec2>,
+}
+
+impl Default for Turret {
+    fn default() -> Self {
+        Self::new(500.0, 2.0, 15.0)
+    }
+}
+
+impl Turret {
+    pub fn new(range: f32, fire_rate: f32, damage: f32) -> Self {
+        Self {
+            range,
+            fire_rate,
+            damage,
+            cooldown: 0.0,
+            target: None,
+        }
+    }
+}
+
+/// The part of a turret that turns to aim, its angle is the way it fires in.
+#[derive(Component)]
+struct Barrel;
+
+/// Spawns `turret` at `pos`, on the player's side.
+pub fn spawn_turret(commands: &mut Commands, pos: Vec2, turret: Turret) -> Entity {
+    commands
+        .spawn((
+            Name::new("Turret"),
+            turret,
+            GameplayEntity,
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::DARK_GREEN,
+                    custom_size: Some(Vec2::splat(2.0 * TURRET_RADIUS)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(0.0)),
+                ..default()
+            },
+            Health::new(TURRET_HEALTH),
+            Faction::Player,
+            RigidBody::Fixed,
+            Collider::ball(TURRET_RADIUS),
+            turret_groups(),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Name::new("Barrel"),
+                    Barrel,
+                    SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, 0.1)),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::GRAY,
+                            custom_size: Some(Vec2::new(BARREL_LENGTH, 10.0)),
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(BARREL_LENGTH / 2.0, 0.0, 0.0),
+                        ..default()
+                    });
+                });
+        })
+        .id()
+}
+
+/// Puts a turret down between a player and where they're aiming, if there aren't too many
+/// already.
+fn place_turret(
+    mut commands: Commands,
+    player: Query<(&ActionState<Action>, &Transform, &AimTarget), With<Player>>,
+    turrets: Query<(), With<Turret>>,
+) {
+    // Turrets placed this frame don't show up in `turrets` yet
+    let mut count = turrets.iter().count();
+    for (actions, tf, aim) in &player {
+        if !actions.just_pressed(Action::PlaceTurret) || count >= MAX_TURRETS {
+            continue;
+        }
+        let player_pos = tf.translation.xy();
+        let pos = player_pos + (aim.point - player_pos).clamp_length_max(PLACE_RANGE);
+        spawn_turret(&mut commands, pos, Turret::default());
+        count += 1;
+    }
+}
+
+/// Picks the nearest enemy in range as each turret's target and turns its barrel towards it,
+/// the same way the player turns towards where they're walking.
+fn aim_turrets(
+    mut turrets: Query<(&Transform, &mut Turret, &Children)>,
+    mut barrels: Query<&mut Transform, (With<Barrel>, Without<Turret>)>,
+    grid: Res<EnemyGrid>,
+    time: Res<GameTime>,
+) {
+    for (tf, mut turret, children) in &mut turrets {
+        let pos = tf.translation.xy();
+        let range = turret.range;
+        turret.target = grid
+            .nearest(pos)
+            .map(|(_, target)| target)
+            .filter(|target| target.distance(pos) <= range);
+        let Some(target) = turret.target else { continue };
+
+        let mut barrels = barrels.iter_many_mut(children);
+        while let Some(mut barrel_tf) = barrels.fetch_next() {
+            let to_target = target - pos;
+            let angle = barrel_tf.rotation.to_euler(EulerRot::ZYX).0;
+            let max_turn = BARREL_TURN_SPEED * time.delta_seconds();
+            let aim = to_target.y.atan2(to_target.x);
+            barrel_tf.rotation = Quat::from_rotation_z(turn_towards(angle, aim, max_turn));
+        }
+    }
+}
+
+/// Fires each turret at its target once it's reloaded and its barrel is pointing at it.
+fn turret_attack(
+    mut turrets: Query<(Entity, &Transform, &mut Turret, &Children)>,
+    barrels: Query<&Transform, With<Barrel>>,
+    audio: Res<Audio>,
+    sounds: Res<SoundEffects>,
+    listener: Res<ListenerPos>,
+    time: Res<GameTime>,
+    tuning: Res<Tuning>,
+    mut fire: EventWriter<FireBullet>,
+) {
+    for (entity, tf, mut turret, children) in &mut turrets {
+        turret.cooldown = (turret.cooldown - time.delta_seconds()).max(0.0);
+        let Some(target) = turret.target else { continue };
+        let Some(barrel_tf) = barrels.iter_many(children).next() else { continue };
+        if turret.cooldown > 0.0 {
+            continue;
+        }
+
+        let pos = tf.translation.xy();
+        let dir = Vec2::from_angle(barrel_tf.rotation.to_euler(EulerRot::ZYX).0);
+        if dir.angle_between(target - pos).abs() > AIM_TOLERANCE {
+            continue;
+        }
+
+        turret.cooldown = 1.0 / turret.fire_rate;
+        fire.send(FireBullet {
+            origin: (pos + BARREL_LENGTH * dir).extend(tf.translation.z),
+            dir,
+            stats: BulletStats {
+                damage: turret.damage,
+                ..BulletStats::tuned(&tuning)
+            },
+            shooter: Shooter {
+                entity,
+                faction: Faction::Player,
+                velocity: Vec2::ZERO,
+                pos,
+                radius: TURRET_RADIUS,
+            },
+        });
+        sounds.play_at(&audio, &sounds.gunshot, 0.3, pos, &listener);
+    }
+}
+
+/// Wears turrets down while enemies are pressed up against them.
+fn turret_contact_damage(
+    mut turrets: Query<(&Transform, &mut Health), With<Turret>>,
+    grid: Res<EnemyGrid>,
+    time: Res<GameTime>,
+) {
+    for (tf, mut health) in &mut turrets {
+        let touching = grid
+            .query_nearby(tf.translation.xy(), TURRET_RADIUS + ENEMY_RADIUS)
+            .count();
+        health.damage(touching as f32 * CONTACT_DAMAGE * time.delta_seconds());
+    }
+}
+
+fn destroy_turrets(mut commands: Commands, turrets: Query<(Entity, &Health), With<Turret>>) {
+    for (entity, health) in &turrets {
+        if health.is_dead() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
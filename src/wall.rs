@@ -1,27 +1,535 @@
+use bevy::asset::LoadState;
+use bevy::ecs::query::ReadOnlyWorldQuery;
+use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::sprite::MaterialMesh2dBundle;
 use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::ArenaBounds;
+use crate::collision::wall_groups;
+use crate::game_time::GameTime;
+use crate::health::Health;
+use crate::hot_reload::load_ron_or;
+use crate::state::{GameState, GameplayEntity};
 
 pub struct WallPlugin;
 
 impl Plugin for WallPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_some_walls);
+        app.insert_resource(Level::load(LEVEL_PATH))
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(spawn_some_walls))
+            .add_system(destroy_walls)
+            .add_system(fall_back_from_missing_textures)
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(patrol));
+    }
+}
+
+/// Where designers can lay out the arena, see [`Level`] for the format.
+const LEVEL_PATH: &str = "assets/level.ron";
+/// Color of walls that aren't given a tint or texture.
+const WALL_COLOR: Color = Color::BLUE;
+/// How thick walls along a polyline are drawn, their colliders have no thickness.
+const POLYLINE_WIDTH: f32 = 4.0;
+/// How thick the walls around the arena are. Thick enough that even the fastest bullets can't
+/// make it through in a single step.
+const BOUNDARY_THICKNESS: f32 = 200.0;
+
+#[derive(Component)]
+pub struct Wall;
+
+/// One of the walls boxing the arena in, see [`spawn_boundary_walls`].
+#[derive(Component)]
+pub struct BoundaryWall;
+
+/// Lets bullets through a wall from one side only, making cover that can be shot out of but not
+/// into. Bullets coming from the side this direction points to, flying against it, pass through.
+#[derive(Component, Debug, Clone, Copy, Deref)]
+pub struct PassableFrom(pub Vec2);
+
+impl PassableFrom {
+    pub fn lets_through(&self, velocity: Vec2) -> bool {
+        velocity.dot(**self) < 0.0
     }
 }
 
-fn spawn_some_walls(mut commands: Commands) {
-    commands.spawn((
-        Name::new("Wall"),
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::BLUE,
-                custom_size: Some(Vec2::new(500.0, 50.0)),
-                ..default()
-            },
-            transform: Transform::from_xyz(0.0, -100.0, 0.0),
+/// What a wall is made of, which decides what sprays off it when it's shot.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SurfaceMaterial {
+    Metal,
+    Wood,
+    Stone,
+}
+
+/// The shape of a wall, centered on its position unless given as points.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WallShape {
+    Cuboid {
+        size: Vec2,
+    },
+    /// A box with its corners rounded off by `radius`, still `size` in total.
+    RoundCuboid {
+        size: Vec2,
+        radius: f32,
+    },
+    /// Handy for ramps and angled cover.
+    Triangle {
+        points: [Vec2; 3],
+    },
+    /// A thin wall along a line through the points.
+    Polyline {
+        points: Vec<Vec2>,
+    },
+}
+
+/// A wall placed in the level, e.g.
+///
+/// ```ron
+/// (pos: (-400.0, 250.0), rotation: 0.5, shape: RoundCuboid(size: (300.0, 40.0), radius: 10.0))
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WallDef {
+    pub pos: Vec2,
+    /// Counterclockwise, in radians.
+    #[serde(default)]
+    pub rotation: f32,
+    pub shape: WallShape,
+    /// Lets bullets coming from this side through, relative to the wall's rotation. See
+    /// [`PassableFrom`].
+    #[serde(default)]
+    pub passable_from: Option<Vec2>,
+    /// Makes the wall move along a path instead of staying put.
+    #[serde(default)]
+    pub patrol: Option<Patrol>,
+    /// Image to draw the wall with, relative to the assets folder, e.g. `"sprites/crate.png"`.
+    /// It's stretched over the whole wall.
+    #[serde(default)]
+    pub texture: Option<String>,
+    /// Color the wall is drawn in, or its texture is multiplied with, e.g.
+    /// `Some(Rgba(red: 0.6, green: 0.4, blue: 0.2, alpha: 1.0))`. Textures are drawn as they
+    /// are without one.
+    #[serde(default)]
+    pub tint: Option<Color>,
+    /// What the wall is made of, e.g. `Some(Wood)`. Walls made of nothing in particular spray
+    /// white debris.
+    #[serde(default)]
+    pub material: Option<SurfaceMaterial>,
+}
+
+impl WallDef {
+    /// How the wall is drawn: its texture if it has one, and the color to draw or tint it in.
+    fn look(&self, asset_server: &AssetServer) -> (Option<Handle<Image>>, Color) {
+        match &self.texture {
+            Some(path) => (
+                Some(asset_server.load(path.as_str())),
+                self.tint.unwrap_or(Color::WHITE),
+            ),
+            None => (None, self.tint.unwrap_or(WALL_COLOR)),
+        }
+    }
+}
+
+/// A wall texture that is still loading. Should it fail to load, the wall is drawn in `fallback`
+/// instead of not at all.
+#[derive(Component, Clone)]
+struct WallTexture {
+    texture: Handle<Image>,
+    fallback: Color,
+}
+
+/// Moves a wall back and forth along a path, e.g.
+///
+/// ```ron
+/// (waypoints: [(-300.0, 500.0), (300.0, 500.0)], speed: 100.0)
+/// ```
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Patrol {
+    /// Points in the world the wall moves between, in order, going back to the first after the
+    /// last.
+    pub waypoints: Vec<Vec2>,
+    pub speed: f32,
+    /// Index of the waypoint the wall is moving towards.
+    #[serde(skip)]
+    next: usize,
+}
+
+/// What the arena is made of, see [`WallDef`] for the format of walls.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct Level {
+    /// Whether the arena is enclosed by walls along its bounds.
+    #[serde(default = "enabled")]
+    pub boundary_walls: bool,
+    /// Where the player starts.
+    #[serde(default = "player_spawn")]
+    pub player_spawn: Vec2,
+    pub walls: Vec<WallDef>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn player_spawn() -> Vec2 {
+    Vec2::new(0.0, 300.0)
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            boundary_walls: true,
+            player_spawn: player_spawn(),
+            walls: vec![
+                WallDef {
+                    pos: Vec2::new(0.0, -100.0),
+                    rotation: 0.0,
+                    passable_from: None,
+                    texture: None,
+                    tint: None,
+                    material: Some(SurfaceMaterial::Stone),
+                    patrol: None,
+                    shape: WallShape::Cuboid {
+                        size: Vec2::new(500.0, 50.0),
+                    },
+                },
+                WallDef {
+                    pos: Vec2::new(-400.0, 250.0),
+                    rotation: 0.5,
+                    passable_from: None,
+                    texture: None,
+                    tint: None,
+                    material: Some(SurfaceMaterial::Wood),
+                    patrol: None,
+                    shape: WallShape::RoundCuboid {
+                        size: Vec2::new(300.0, 40.0),
+                        radius: 10.0,
+                    },
+                },
+                WallDef {
+                    pos: Vec2::new(-300.0, 500.0),
+                    rotation: 0.0,
+                    passable_from: None,
+                    texture: None,
+                    tint: None,
+                    material: Some(SurfaceMaterial::Metal),
+                    patrol: Some(Patrol {
+                        waypoints: vec![Vec2::new(-300.0, 500.0), Vec2::new(300.0, 500.0)],
+                        speed: 100.0,
+                        next: 0,
+                    }),
+                    shape: WallShape::Cuboid {
+                        size: Vec2::new(150.0, 30.0),
+                    },
+                },
+                WallDef {
+                    pos: Vec2::new(450.0, 200.0),
+                    rotation: 0.0,
+                    passable_from: None,
+                    texture: None,
+                    tint: None,
+                    material: None,
+                    patrol: None,
+                    shape: WallShape::Triangle {
+                        points: [
+                            Vec2::new(-100.0, -60.0),
+                            Vec2::new(100.0, -60.0),
+                            Vec2::new(100.0, 60.0),
+                        ],
+                    },
+                },
+            ],
+        }
+    }
+}
+
+impl Level {
+    /// Reads the level from `path`, falling back to the built-in one if it's missing or invalid.
+    fn load(path: &str) -> Self {
+        load_ron_or(path, "default level", Self::default)
+    }
+}
+
+/// Spawns four walls just outside `bounds`, boxing it in. Bullets bounce off them like any other
+/// wall, but they're never visible as the camera doesn't show anything outside the arena, and
+/// the minimap leaves them off as its edges already show where they are.
+pub fn spawn_boundary_walls(commands: &mut Commands, bounds: &ArenaBounds) {
+    let center = (bounds.min + bounds.max) / 2.0;
+    let half_size = bounds.size() / 2.0;
+    let half_thickness = BOUNDARY_THICKNESS / 2.0;
+    // Horizontal walls reach over the corners so there are no gaps between them
+    let horizontal = Vec2::new(half_size.x + BOUNDARY_THICKNESS, half_thickness);
+    let vertical = Vec2::new(half_thickness, half_size.y);
+    let walls = [
+        (Vec2::new(0.0, half_size.y + half_thickness), horizontal),
+        (Vec2::new(0.0, -half_size.y - half_thickness), horizontal),
+        (Vec2::new(half_size.x + half_thickness, 0.0), vertical),
+        (Vec2::new(-half_size.x - half_thickness, 0.0), vertical),
+    ];
+    for (offset, half_extents) in walls {
+        commands.spawn((
+            Name::new("Boundary wall"),
+            (Wall, BoundaryWall),
+            GameplayEntity,
+            TransformBundle::from_transform(Transform::from_translation(
+                (center + offset).extend(0.0),
+            )),
+            RigidBody::Fixed,
+            Collider::cuboid(half_extents.x, half_extents.y),
+            wall_groups(),
+        ));
+    }
+}
+
+/// Moves a circle of `radius` at `pos` out of the walls in `walls` it overlaps, the shortest way
+/// out of each. Kinematic bodies are never stopped or pushed by Rapier, so anything moved by hand
+/// that shouldn't pass through walls is moved out of them with this. `filter` picks which
+/// colliders are checked, only those also in `walls` are moved out of.
+pub fn push_out_of_walls<F: ReadOnlyWorldQuery>(
+    pos: Vec2,
+    radius: f32,
+    rapier: &RapierContext,
+    filter: QueryFilter,
+    walls: &Query<(&Collider, &GlobalTransform), F>,
+) -> Vec2 {
+    let mut pos = pos;
+    let shape = Collider::ball(radius);
+    rapier.intersections_with_shape(pos, 0.0, &shape, filter, |entity| {
+        let Ok((collider, tf)) = walls.get(entity) else { return true };
+        let (_, rotation, translation) = tf.to_scale_rotation_translation();
+        let angle = rotation.to_euler(EulerRot::ZYX).0;
+        let projection = collider.project_point(translation.xy(), angle, pos, false);
+        // From inside the wall the closest point on its edge is the way out, from outside it's
+        // the way into the wall
+        let offset = projection.point - pos;
+        let out = if projection.is_inside {
+            offset
+        } else {
+            -offset
+        };
+        if let Some(out) = out.try_normalize() {
+            if projection.is_inside || offset.length() < radius {
+                pos = projection.point + radius * out;
+            }
+        }
+        true
+    });
+    pos
+}
+
+/// A single triangle with the given corners.
+fn triangle_mesh(points: [Vec2; 3]) -> Mesh {
+    let [a, b, c] = points;
+    // Wound counterclockwise, whichever way the corners were given
+    let points = if (b - a).perp_dot(c - a) >= 0.0 {
+        [a, b, c]
+    } else {
+        [a, c, b]
+    };
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        points.map(|point| [point.x, point.y, 0.0]).to_vec(),
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 3]);
+    // Textures are stretched over the triangle's bounding box
+    let min = points[0].min(points[1]).min(points[2]);
+    let size = (points[0].max(points[1]).max(points[2]) - min).max(Vec2::splat(f32::EPSILON));
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        points
+            .map(|point| {
+                let uv = (point - min) / size;
+                [uv.x, 1.0 - uv.y]
+            })
+            .to_vec(),
+    );
+    mesh.set_indices(Some(Indices::U32(vec![0, 1, 2])));
+    mesh
+}
+
+/// Spawns a wall as described by `def`, with a collider and sprite or mesh of the same shape.
+pub fn spawn_wall(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    asset_server: &AssetServer,
+    def: &WallDef,
+) {
+    let (texture, color) = def.look(asset_server);
+    let loading = texture.clone().map(|texture| WallTexture {
+        texture,
+        fallback: def.tint.unwrap_or(WALL_COLOR),
+    });
+    let transform = Transform::from_translation(def.pos.extend(0.0))
+        .with_rotation(Quat::from_rotation_z(def.rotation));
+    let mut wall = commands.spawn((Name::new("Wall"), Wall, GameplayEntity, wall_groups()));
+    if let Some(patrol) = &def.patrol {
+        // Rapier moves the collider along with the velocity `patrol` sets
+        wall.insert((
+            RigidBody::KinematicVelocityBased,
+            Velocity::zero(),
+            patrol.clone(),
+        ));
+    } else {
+        wall.insert(RigidBody::Fixed);
+    }
+    if let Some(material) = def.material {
+        wall.insert(material);
+    }
+    if let Some(from) = def.passable_from {
+        wall.insert(PassableFrom(Vec2::from_angle(def.rotation).rotate(from)));
+    }
+    match &def.shape {
+        WallShape::Cuboid { size } => {
+            wall.insert((
+                Collider::cuboid(size.x / 2.0, size.y / 2.0),
+                wall_sprite(*size, transform, texture, color),
+            ));
+        }
+        WallShape::RoundCuboid { size, radius } => {
+            // The collider's border is added around the inner box, keep the total size the same
+            let radius = radius.clamp(0.0, size.min_element() / 2.0);
+            let inner = *size / 2.0 - radius;
+            wall.insert((
+                Collider::round_cuboid(inner.x, inner.y, radius),
+                wall_sprite(*size, transform, texture, color),
+            ));
+        }
+        WallShape::Triangle { points } => {
+            wall.insert((
+                Collider::triangle(points[0], points[1], points[2]),
+                MaterialMesh2dBundle {
+                    mesh: meshes.add(triangle_mesh(*points)).into(),
+                    material: materials.add(ColorMaterial { color, texture }),
+                    transform,
+                    ..default()
+                },
+            ));
+        }
+        WallShape::Polyline { points } => {
+            wall.insert((
+                Collider::polyline(points.clone(), None),
+                SpatialBundle::from_transform(transform),
+            ))
+            .with_children(|parent| {
+                for segment in points.windows(2) {
+                    let (start, end) = (segment[0], segment[1]);
+                    let dir = end - start;
+                    let transform = Transform::from_translation(((start + end) / 2.0).extend(0.0))
+                        .with_rotation(Quat::from_rotation_z(dir.y.atan2(dir.x)));
+                    let size = Vec2::new(dir.length(), POLYLINE_WIDTH);
+                    let mut segment =
+                        parent.spawn(wall_sprite(size, transform, texture.clone(), color));
+                    if let Some(loading) = &loading {
+                        segment.insert(loading.clone());
+                    }
+                }
+            });
+            // The segments are what's drawn, not the wall itself
+            return;
+        }
+    }
+    if let Some(loading) = loading {
+        wall.insert(loading);
+    }
+}
+
+fn wall_sprite(
+    size: Vec2,
+    transform: Transform,
+    texture: Option<Handle<Image>>,
+    color: Color,
+) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(size),
             ..default()
         },
-        RigidBody::Fixed,
-        Collider::cuboid(250.0, 25.0),
-    ));
+        transform,
+        texture: texture.unwrap_or_default(),
+        ..default()
+    }
+}
+
+fn spawn_some_walls(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    level: Res<Level>,
+    bounds: Res<ArenaBounds>,
+) {
+    if level.boundary_walls {
+        spawn_boundary_walls(&mut commands, &bounds);
+    }
+    for def in &level.walls {
+        spawn_wall(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &asset_server,
+            def,
+        );
+    }
+}
+
+/// Draws walls whose texture couldn't be loaded in their fallback color, leaving the ones that
+/// did load alone from then on.
+fn fall_back_from_missing_textures(
+    mut commands: Commands,
+    mut walls: Query<(
+        Entity,
+        &WallTexture,
+        Option<(&mut Sprite, &mut Handle<Image>)>,
+        Option<&Handle<ColorMaterial>>,
+    )>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (entity, loading, sprite, material) in &mut walls {
+        match asset_server.get_load_state(&loading.texture) {
+            LoadState::Loaded => {}
+            LoadState::Failed | LoadState::Unloaded => {
+                let path = asset_server.get_handle_path(&loading.texture);
+                warn!("Couldn't load wall texture {path:?}, drawing the wall without it");
+                if let Some((mut sprite, mut texture)) = sprite {
+                    sprite.color = loading.fallback;
+                    *texture = default();
+                }
+                if let Some(material) = material.and_then(|material| materials.get_mut(material)) {
+                    material.color = loading.fallback;
+                    material.texture = None;
+                }
+            }
+            LoadState::NotLoaded | LoadState::Loading => continue,
+        }
+        commands.entity(entity).remove::<WallTexture>();
+    }
+}
+
+/// Moves patrolling walls towards their next waypoint, slowing down to stop right on it.
+fn patrol(mut walls: Query<(&Transform, &mut Velocity, &mut Patrol)>, time: Res<GameTime>) {
+    let dt = time.delta_seconds();
+    for (tf, mut velocity, mut patrol) in &mut walls {
+        if patrol.waypoints.is_empty() || dt <= 0.0 {
+            velocity.linvel = Vec2::ZERO;
+            continue;
+        }
+        let pos = tf.translation.xy();
+        if pos.distance(patrol.waypoints[patrol.next]) < 1.0 {
+            patrol.next = (patrol.next + 1) % patrol.waypoints.len();
+        }
+        let to_next = patrol.waypoints[patrol.next] - pos;
+        velocity.linvel = to_next.clamp_length_max(patrol.speed * dt) / dt;
+    }
+}
+
+/// Walls with `Health` are destructible, and are removed once it runs out.
+fn destroy_walls(mut commands: Commands, walls: Query<(Entity, &Health), With<Wall>>) {
+    for (entity, health) in &walls {
+        if health.is_dead() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
 }
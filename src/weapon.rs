@@ -0,0 +1,483 @@
+use bevy::prelude::*;
+use bevy_hanabi::prelude::*;
+
+use crate::bullet::{BulletRange, BulletStats, DamageFalloff};
+use crate::game_time::GameTime;
+use crate::settings::GraphicsQuality;
+use crate::state::GameState;
+use crate::tuning::Tuning;
+
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_weapon_effects)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(finish_reloads)
+                    .with_system(cool_weapons),
+            )
+            .add_system(retune_loadouts);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FireMode {
+    /// Fires once each time the trigger is pulled.
+    Semi,
+    /// Keeps firing for as long as the trigger is held.
+    Auto,
+    /// Holding the trigger charges up a stronger shot, fired on release.
+    Charged,
+    /// Fires a few rounds in quick succession each time the trigger is pulled, the fire rate is
+    /// how often a burst can be started.
+    Burst(Burst),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Burst {
+    /// Rounds fired per trigger pull.
+    pub rounds: u32,
+    /// Seconds between the rounds of a burst.
+    pub interval: f32,
+    /// Whether letting go of the trigger stops the burst, otherwise it's always fired in full.
+    pub cancel_on_release: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Weapon {
+    pub name: &'static str,
+    pub fire_mode: FireMode,
+    /// Shots per second.
+    pub fire_rate: f32,
+    /// Bullets fired per shot, each at random within `spread`.
+    pub pellets: u32,
+    /// Angle, in radians, of the cone pellets are spread over.
+    pub spread: f32,
+    pub capacity: u32,
+    pub reload_time: f32,
+    pub bullet: BulletStats,
+    /// Rounds left in this weapon's magazine while it's not the one being held.
+    rounds: u32,
+    /// Rounds still to be fired of the burst in progress.
+    pub burst_left: u32,
+    /// For weapons that overheat instead of using up rounds, the [`Heat`] each shot adds.
+    pub heat_per_shot: Option<f32>,
+}
+
+impl Weapon {
+    pub fn pistol(tuning: &Tuning) -> Self {
+        Self {
+            name: "Pistol",
+            fire_mode: FireMode::Semi,
+            fire_rate: 6.0,
+            pellets: 1,
+            spread: 0.0,
+            capacity: 12,
+            reload_time: 1.0,
+            bullet: BulletStats::tuned(tuning),
+            rounds: 12,
+            burst_left: 0,
+            heat_per_shot: None,
+        }
+    }
+
+    pub fn shotgun(tuning: &Tuning) -> Self {
+        Self {
+            name: "Shotgun",
+            fire_mode: FireMode::Semi,
+            fire_rate: 1.2,
+            pellets: 8,
+            spread: 0.4,
+            capacity: 6,
+            reload_time: 2.0,
+            bullet: BulletStats {
+                damage: 10.0,
+                size: 2.0,
+                // Pellets only reach so far, however fast they fly
+                range: BulletRange::Distance(500.0),
+                falloff: Some(DamageFalloff {
+                    start: 150.0,
+                    end: 450.0,
+                    min_damage: 3.0,
+                }),
+                color: Color::ORANGE,
+                ..BulletStats::tuned(tuning)
+            },
+            rounds: 6,
+            burst_left: 0,
+            heat_per_shot: None,
+        }
+    }
+
+    pub fn smg(tuning: &Tuning) -> Self {
+        Self {
+            name: "SMG",
+            fire_mode: FireMode::Auto,
+            fire_rate: 14.0,
+            pellets: 1,
+            spread: 0.08,
+            capacity: 40,
+            reload_time: 1.5,
+            bullet: BulletStats {
+                damage: 12.0,
+                size: 2.0,
+                color: Color::CYAN,
+                ..BulletStats::tuned(tuning)
+            },
+            rounds: 40,
+            burst_left: 0,
+            heat_per_shot: None,
+        }
+    }
+
+    pub fn burst_rifle(tuning: &Tuning) -> Self {
+        Self {
+            name: "Burst rifle",
+            fire_mode: FireMode::Burst(Burst {
+                rounds: 3,
+                interval: 0.07,
+                cancel_on_release: false,
+            }),
+            fire_rate: 3.0,
+            pellets: 1,
+            spread: 0.03,
+            capacity: 24,
+            reload_time: 1.5,
+            bullet: BulletStats {
+                damage: 18.0,
+                color: Color::LIME_GREEN,
+                ..BulletStats::tuned(tuning)
+            },
+            rounds: 24,
+            burst_left: 0,
+            heat_per_shot: None,
+        }
+    }
+
+    pub fn laser(tuning: &Tuning) -> Self {
+        Self {
+            name: "Laser",
+            fire_mode: FireMode::Auto,
+            fire_rate: 12.0,
+            pellets: 1,
+            spread: 0.02,
+            // Never runs out of rounds, only overheats
+            capacity: 0,
+            reload_time: 0.0,
+            bullet: BulletStats {
+                damage: 8.0,
+                size: 2.0,
+                color: Color::FUCHSIA,
+                ..BulletStats::tuned(tuning)
+            },
+            rounds: 0,
+            burst_left: 0,
+            heat_per_shot: Some(9.0),
+        }
+    }
+
+    pub fn charge_rifle(tuning: &Tuning) -> Self {
+        Self {
+            name: "Charge rifle",
+            fire_mode: FireMode::Charged,
+            fire_rate: 2.0,
+            pellets: 1,
+            spread: 0.0,
+            capacity: 5,
+            reload_time: 2.0,
+            bullet: BulletStats {
+                // Punches through the first couple of enemies in its way
+                pierce: 2,
+                color: Color::rgb(0.6, 0.6, 1.0),
+                ..BulletStats::tuned(tuning)
+            },
+            rounds: 5,
+            burst_left: 0,
+            heat_per_shot: None,
+        }
+    }
+
+    pub fn grenade_launcher(tuning: &Tuning) -> Self {
+        Self {
+            name: "Grenade launcher",
+            fire_mode: FireMode::Semi,
+            fire_rate: 1.0,
+            pellets: 1,
+            spread: 0.0,
+            capacity: 4,
+            reload_time: 2.5,
+            bullet: BulletStats {
+                speed: 700.0,
+                damage: 80.0,
+                size: 8.0,
+                gravity: 300.0,
+                explosive: 120.0,
+                // Goes off when the fuse burns down, after bouncing around a bit
+                fused: true,
+                bounces: 3,
+                crit_chance: 0.0,
+                range: BulletRange::Time(1.5),
+                color: Color::DARK_GREEN,
+                ..BulletStats::tuned(tuning)
+            },
+            rounds: 4,
+            burst_left: 0,
+            heat_per_shot: None,
+        }
+    }
+
+    /// Lobs grenades that stun enemies around where they go off rather than hurting them.
+    pub fn stun_grenade(tuning: &Tuning) -> Self {
+        Self {
+            name: "Stun grenade",
+            fire_mode: FireMode::Semi,
+            fire_rate: 0.8,
+            pellets: 1,
+            spread: 0.0,
+            capacity: 3,
+            reload_time: 3.0,
+            bullet: BulletStats {
+                speed: 650.0,
+                damage: 0.0,
+                size: 8.0,
+                gravity: 300.0,
+                explosive: 160.0,
+                fused: true,
+                bounces: 3,
+                stun: 3.0,
+                crit_chance: 0.0,
+                range: BulletRange::Time(1.2),
+                color: Color::GOLD,
+                ..BulletStats::tuned(tuning)
+            },
+            rounds: 3,
+            burst_left: 0,
+            heat_per_shot: None,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Loadout {
+    pub weapons: Vec<Weapon>,
+    pub current: usize,
+    /// Time left until the current weapon can fire again.
+    pub cooldown: f32,
+}
+
+impl Loadout {
+    pub fn new(tuning: &Tuning) -> Self {
+        Self {
+            weapons: vec![
+                Weapon::pistol(tuning),
+                Weapon::shotgun(tuning),
+                Weapon::smg(tuning),
+                Weapon::charge_rifle(tuning),
+                Weapon::burst_rifle(tuning),
+                Weapon::laser(tuning),
+                Weapon::grenade_launcher(tuning),
+                Weapon::stun_grenade(tuning),
+            ],
+            current: 0,
+            cooldown: 0.0,
+        }
+    }
+
+    pub fn current(&self) -> &Weapon {
+        &self.weapons[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Weapon {
+        &mut self.weapons[self.current]
+    }
+
+    /// The magazine of the weapon held when the loadout is first equipped.
+    pub fn magazine(&self) -> Magazine {
+        Magazine {
+            rounds: self.current().rounds,
+            capacity: self.current().capacity,
+        }
+    }
+
+    /// Holsters the current weapon, keeping the rounds left in `magazine`, and swaps `magazine`
+    /// for the one of the weapon at `index`.
+    pub fn switch_to(&mut self, index: usize, magazine: &mut Magazine) {
+        if index == self.current || index >= self.weapons.len() {
+            return;
+        }
+        self.weapons[self.current].rounds = magazine.rounds;
+        self.weapons[self.current].burst_left = 0;
+        self.current = index;
+        self.cooldown = 0.0;
+        *magazine = self.magazine();
+    }
+
+    /// Rounds left in every weapon's magazine, with `magazine` being the held weapon's.
+    pub fn rounds(&self, magazine: &Magazine) -> Vec<u32> {
+        let mut rounds: Vec<_> = self.weapons.iter().map(|weapon| weapon.rounds).collect();
+        rounds[self.current] = magazine.rounds;
+        rounds
+    }
+
+    /// Holds the weapon at `current` with every magazine filled to the given rounds, undoing
+    /// [`Loadout::rounds`]. Weapons without a count keep what they have. An empty loadout has
+    /// nothing to hold and is left as it is.
+    pub fn restore(&mut self, current: usize, rounds: &[u32], magazine: &mut Magazine) {
+        if self.weapons.is_empty() {
+            return;
+        }
+        for (weapon, &rounds) in self.weapons.iter_mut().zip(rounds) {
+            weapon.rounds = rounds.min(weapon.capacity);
+        }
+        self.current = current.min(self.weapons.len() - 1);
+        self.cooldown = 0.0;
+        *magazine = self.magazine();
+    }
+
+    /// Gives every weapon the bullets it would have with `tuning`, keeping the rounds it has.
+    pub fn retune(&mut self, tuning: &Tuning) {
+        for (weapon, tuned) in self.weapons.iter_mut().zip(Self::new(tuning).weapons) {
+            weapon.bullet = tuned.bullet;
+        }
+    }
+
+    /// Switches to the weapon at `index` and fills its magazine.
+    pub fn equip_full(&mut self, index: usize, magazine: &mut Magazine) {
+        if index >= self.weapons.len() {
+            return;
+        }
+        self.switch_to(index, magazine);
+        magazine.rounds = magazine.capacity;
+    }
+}
+
+/// The magazine of the weapon currently being held.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Magazine {
+    pub rounds: u32,
+    pub capacity: u32,
+}
+
+/// Fraction of the maximum heat an overheated weapon has to cool down to before it fires again.
+const OVERHEAT_RESUME: f32 = 0.3;
+
+/// Builds up while firing weapons that overheat, shared by all of them. Overheating locks them
+/// until they've cooled down.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Heat {
+    pub current: f32,
+    pub max: f32,
+    /// Heat lost per second while not firing.
+    pub cool_rate: f32,
+    /// Whether firing is locked until the heat has dropped below [`OVERHEAT_RESUME`] of the max.
+    pub overheated: bool,
+}
+
+impl Default for Heat {
+    fn default() -> Self {
+        Self {
+            current: 0.0,
+            max: 100.0,
+            cool_rate: 40.0,
+            overheated: false,
+        }
+    }
+}
+
+impl Heat {
+    /// Heats up by `amount`, returning whether that made it overheat.
+    pub fn add(&mut self, amount: f32) -> bool {
+        self.current = (self.current + amount).min(self.max);
+        let overheats = !self.overheated && self.current >= self.max;
+        self.overheated |= overheats;
+        overheats
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct WeaponEffects {
+    /// Vented when a weapon overheats.
+    pub steam: Handle<EffectAsset>,
+}
+
+/// The magazine is refilled once the timer finishes.
+#[derive(Component, Deref, DerefMut)]
+pub struct Reloading(pub Timer);
+
+/// How charged the current shot is, from 0 to 1.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct Charge(pub f32);
+
+fn finish_reloads(
+    mut commands: Commands,
+    mut reloading: Query<(Entity, &mut Reloading, &mut Magazine)>,
+    time: Res<GameTime>,
+) {
+    for (entity, mut reload, mut magazine) in &mut reloading {
+        if reload.tick(time.delta()).finished() {
+            magazine.rounds = magazine.capacity;
+            commands.entity(entity).remove::<Reloading>();
+        }
+    }
+}
+
+/// Applies a reloaded tuning to the weapons that are already equipped.
+fn retune_loadouts(tuning: Res<Tuning>, mut loadouts: Query<&mut Loadout>) {
+    if !tuning.is_changed() {
+        return;
+    }
+    for mut loadout in &mut loadouts {
+        loadout.retune(&tuning);
+    }
+}
+
+/// Cools weapons down while they aren't firing.
+fn cool_weapons(mut weapons: Query<(&mut Heat, &Loadout)>, time: Res<GameTime>) {
+    for (mut heat, loadout) in &mut weapons {
+        // The cooldown only runs between shots, so it's over once the trigger is let go
+        if loadout.cooldown > 0.0 {
+            continue;
+        }
+        heat.current = (heat.current - heat.cool_rate * time.delta_seconds()).max(0.0);
+        if heat.overheated && heat.current <= heat.max * OVERHEAT_RESUME {
+            heat.overheated = false;
+        }
+    }
+}
+
+fn setup_weapon_effects(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    quality: Res<GraphicsQuality>,
+) {
+    let mut gradient = Gradient::new();
+    gradient.add_key(0.0, Vec4::new(0.9, 0.9, 0.9, 0.6));
+    gradient.add_key(1.0, Vec4::new(0.9, 0.9, 0.9, 0.0));
+
+    let steam = effects.add(
+        EffectAsset {
+            name: "Steam".into(),
+            capacity: quality.capacity(512),
+            spawner: Spawner::once(quality.particles(60.0).into(), true),
+            ..default()
+        }
+        .init(InitPositionCircleModifier {
+            radius: 6.0,
+            dimension: ShapeDimension::Volume,
+            ..default()
+        })
+        .init(InitVelocityCircleModifier {
+            speed: Value::Uniform((20.0, 80.0)),
+            ..default()
+        })
+        .init(InitLifetimeModifier {
+            lifetime: Value::Uniform((0.4, 0.9)),
+        })
+        .render(SizeOverLifetimeModifier {
+            gradient: Gradient::constant(Vec2::splat(3.0)),
+        })
+        .render(ColorOverLifetimeModifier { gradient }),
+    );
+    commands.insert_resource(WeaponEffects { steam });
+}